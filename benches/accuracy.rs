@@ -0,0 +1,74 @@
+// speed vs accuracy table for every IDCT
+// cargo bench --bench accuracy
+
+use std::hint::black_box;
+use std::time::Instant;
+
+use idct_test::accuracy;
+use idct_test::idct;
+use idct_test::quant::LUMINANCE;
+use idct_test::transform::*;
+
+const BLOCKS: usize = 10000;
+
+fn row<T: InverseDct>(corpus: &[[i32;64]]) {
+    let mut out = [0_u8;64];
+    let start = Instant::now();
+    for f in corpus {
+        T::idct_into(black_box(f), &mut out);
+        black_box(&out);
+    }
+    let ns = start.elapsed().as_nanos() as f64 / corpus.len() as f64;
+
+    let r = accuracy::report::<T>(corpus);
+    println!("{:<18} {:>10.1} {:>8} {:>9.2} {:>9.4} {:>10.4} {:>10}",
+        T::NAME, ns, r.max_abs_error, r.psnr, r.mse, r.mean_error, r.off_by_one);
+}
+
+fn table(title: &str, corpus: &[[i32;64]]) {
+    println!("{} ({} blocks, reference idct_f64)", title, corpus.len());
+    println!("{:<18} {:>10} {:>8} {:>9} {:>9} {:>10} {:>10}",
+        "algorithm", "ns/block", "max err", "PSNR dB", "MSE", "mean err", "off by 1");
+    row::<StandardIdct>(corpus);
+    row::<LlmIdct>(corpus);
+    row::<AanIdct>(corpus);
+    row::<AanIdctF64>(corpus);
+    row::<Ap922Idct>(corpus);
+    row::<IslowIdct>(corpus);
+    row::<Idct7>(corpus);
+    row::<Idct8>(corpus);
+    row::<Idct9>(corpus);
+    println!();
+}
+
+fn quantize(corpus: &[[i32;64]]) -> Vec<[i32;64]> {
+    corpus.iter().map(|f| {
+        let mut q = [0;64];
+        for i in 0..64 {
+            q[i] = (f[i] as f32 / LUMINANCE[i] as f32).round() as i32;
+        }
+        q
+    }).collect()
+}
+
+// how far the float AAN is from the pixels a libjpeg ifast decoder shows
+fn fast_against_ifast(title: &str, corpus: &[[i32;64]]) {
+    let fast = idct::fast_prepare_quant(&LUMINANCE);
+    let ifast = idct::ifast_prepare_quant(&LUMINANCE);
+    let r = accuracy::compare(
+        |f, out| idct::ifast_idct_dequant(f, &ifast, out),
+        |f, out| idct::fast_idct_dequant(f, &fast, out),
+        &quantize(corpus));
+    println!("fast_idct against ifast, {} quantized with Annex K luma", title);
+    println!("max err {}  PSNR {:.2} dB  MSE {:.4}  mean err {:.4}  off by 1 {}  mismatched {}",
+        r.max_abs_error, r.psnr, r.mse, r.mean_error, r.off_by_one, r.mismatched);
+    println!();
+}
+
+fn main() {
+    table("IEEE 1180 L=256 H=255", &accuracy::ieee1180_corpus(BLOCKS, 256, 255, 1));
+    table("IEEE 1180 L=5 H=5", &accuracy::ieee1180_corpus(BLOCKS, 5, 5, 1));
+    table("low frequency 4 coefficients", &accuracy::random_corpus(BLOCKS, 300, 4));
+    fast_against_ifast("IEEE 1180 L=256 H=255", &accuracy::ieee1180_corpus(BLOCKS, 256, 255, 1));
+    fast_against_ifast("IEEE 1180 L=5 H=5", &accuracy::ieee1180_corpus(BLOCKS, 5, 5, 1));
+}
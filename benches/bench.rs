@@ -3,6 +3,14 @@ use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 use idct_test::idct;
 use idct_test::fdct;
+use idct_test::zigzag;
+use idct_test::quant;
+use idct_test::huffman::HuffmanSpec;
+use idct_test::trellis;
+use idct_test::scaled::ScaledIdct;
+use idct_test::decoder;
+use idct_test::encoder::{self, EncodeOptions};
+use idct_test::transform::{AanFdct, AanIdct, IslowIdct, LlmFdct, LlmIdct};
 
 const ZZ :[i32;64] = [
       568,     0,     0,    -4,    -4,     0,     4,     0 ,
@@ -14,6 +22,37 @@ const ZZ :[i32;64] = [
        -5,     0,     0,     0,     0,     0,     0,     0 ,
         0,     0,     0,     0,     0,     0,     0,     1 ];
 
+// sparse blocks as most of a real JPEG, (name, block, zigzag eob)
+const SPARSE :[(&str, [i32;64], usize);3] = [
+    ("DC only", [
+      568,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ], 0),
+    ("2x2", [
+      568,    12,     0,     0,     0,     0,     0,     0 ,
+      -27,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ], 2),
+    ("4x4", [
+      568,    12,     0,    -4,     0,     0,     0,     0 ,
+      -27,     9,    -4,     0,     0,     0,     0,     0 ,
+      -49,    -4,     0,     0,     0,     0,     0,     0 ,
+      -12,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ,
+        0,     0,     0,     0,     0,     0,     0,     0 ], 9),
+];
+
 const Z :[u8;64] = [
             128,   128,   128,   128,   128,   128,   128,   128 ,
             128,   255,   255,   255,   255,   255,   255,   255 ,
@@ -60,6 +99,233 @@ fn llm_idct(c: &mut Criterion) {
     );
 }
 
+fn llm_idct_into(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "LLM IDCT into",
+        |b| b.iter(|| idct::llm_idct_into(black_box(&ZZ), &mut out))
+    );
+}
+
+fn aan_idct_into(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "AAN IDCT into",
+        |b| b.iter(|| idct::fast_idct_into(black_box(&ZZ), &mut out))
+    );
+}
+
+fn llm_idct_dequant(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    let table = idct::llm_prepare_quant(&[1;64]);
+    c.bench_function(
+        "LLM IDCT dequant",
+        |b| b.iter(|| idct::llm_idct_dequant(black_box(&ZZ), &table, &mut out))
+    );
+}
+
+// the same block read in zigzag order by the first pass
+fn zigzag_idct(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    let zz = zigzag::to_zigzag(&ZZ);
+    let table = idct::llm_prepare_quant(&[1;64]);
+    let ifast = idct::ifast_prepare_quant(&[1;64]);
+    c.bench_function(
+        "LLM IDCT zigzag dequant",
+        |b| b.iter(|| zigzag::llm_idct_dequant(black_box(&zz), &table, &mut out))
+    );
+    c.bench_function(
+        "ISLOW IDCT zigzag",
+        |b| b.iter(|| zigzag::islow_idct_into(black_box(&zz), &mut out))
+    );
+    c.bench_function(
+        "IFAST IDCT zigzag dequant",
+        |b| b.iter(|| zigzag::ifast_idct_dequant(black_box(&zz), &ifast, &mut out))
+    );
+}
+
+fn aan_idct_dequant(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    let table = idct::fast_prepare_quant(&[1;64]);
+    c.bench_function(
+        "AAN IDCT dequant",
+        |b| b.iter(|| idct::fast_idct_dequant(black_box(&ZZ), &table, &mut out))
+    );
+}
+
+fn ifast_idct_dequant(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    let table = idct::ifast_prepare_quant(&[1;64]);
+    c.bench_function(
+        "IFAST IDCT dequant",
+        |b| b.iter(|| idct::ifast_idct_dequant(black_box(&ZZ), &table, &mut out))
+    );
+}
+
+fn idct_4x4(c: &mut Criterion) {
+    let mut out = [0_u8;16];
+    c.bench_function(
+        "IDCT 4x4",
+        |b| b.iter(|| idct::idct_4x4_into(black_box(&ZZ), &mut out))
+    );
+}
+
+fn idct_2x2(c: &mut Criterion) {
+    let mut out = [0_u8;4];
+    c.bench_function(
+        "IDCT 2x2",
+        |b| b.iter(|| idct::idct_2x2_into(black_box(&ZZ), &mut out))
+    );
+}
+
+fn scaled_idct(c: &mut Criterion) {
+    let mut out = [0_u8;256];
+    for n in [3, 4, 12, 16] {
+        let scaled = ScaledIdct::new(n);
+        c.bench_function(
+            &format!("scaled IDCT {}x{}", n, n),
+            |b| b.iter(|| scaled.idct_into(black_box(&ZZ), &mut out[..n * n]))
+        );
+    }
+}
+
+fn sparse_idct(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    for (name, f, eob) in SPARSE.iter() {
+        c.bench_function(
+            &format!("LLM IDCT {}", name),
+            |b| b.iter(|| idct::llm_idct_into(black_box(f), &mut out))
+        );
+        c.bench_function(
+            &format!("LLM IDCT eob {}", name),
+            |b| b.iter(|| idct::llm_idct_eob(black_box(f), black_box(*eob), &mut out))
+        );
+        c.bench_function(
+            &format!("AP922 IDCT {}", name),
+            |b| b.iter(|| idct::ap922_idct_into(black_box(f), &mut out))
+        );
+        c.bench_function(
+            &format!("AP922 IDCT eob {}", name),
+            |b| b.iter(|| idct::ap922_idct_eob(black_box(f), black_box(*eob), &mut out))
+        );
+    }
+}
+
+fn islow_idct(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "ISLOW IDCT",
+        |b| b.iter(|| idct::islow_idct_into(black_box(&ZZ), &mut out))
+    );
+}
+
+#[cfg(target_arch = "x86_64")]
+fn simd_idct(c: &mut Criterion) {
+    use idct_test::x86;
+
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "LLM IDCT SSE2",
+        |b| b.iter(|| x86::llm_idct_sse2(black_box(&ZZ), &mut out))
+    );
+    c.bench_function(
+        "ISLOW IDCT SSE2",
+        |b| b.iter(|| x86::islow_idct_sse2(black_box(&ZZ), &mut out))
+    );
+    if is_x86_feature_detected!("avx2") {
+        c.bench_function(
+            "LLM IDCT AVX2",
+            |b| b.iter(|| unsafe { x86::llm_idct_avx2(black_box(&ZZ), &mut out) })
+        );
+        c.bench_function(
+            "ISLOW IDCT AVX2",
+            |b| b.iter(|| unsafe { x86::islow_idct_avx2(black_box(&ZZ), &mut out) })
+        );
+    }
+}
+
+#[cfg(not(target_arch = "x86_64"))]
+fn simd_idct(_c: &mut Criterion) {}
+
+// a 64 block component, one call per block against one call for all
+fn batch_idct(c: &mut Criterion) {
+    use idct_test::batch;
+
+    let f: Vec<i32> = (0..64).flat_map(|_| ZZ).collect();
+    let mut out = vec![0_u8; f.len()];
+    let algorithms: [(&str, batch::BlocksFn, idct::IdctFn);3] = [
+        ("LLM", batch::llm_idct_blocks, idct::llm_idct_into),
+        ("AAN", batch::fast_idct_blocks, idct::fast_idct_into),
+        ("ISLOW", batch::islow_idct_blocks, idct::islow_idct_into),
+    ];
+    for (name, blocks, single) in algorithms {
+        c.bench_function(
+            &format!("{} IDCT 64 blocks single", name),
+            |b| b.iter(|| batch::idct_blocks_with(single, black_box(&f), &mut out))
+        );
+        c.bench_function(
+            &format!("{} IDCT 64 blocks batch", name),
+            |b| b.iter(|| blocks(black_box(&f), &mut out))
+        );
+    }
+}
+
+// a 1920x1080 luma plane, 240 x 135 blocks
+// cargo bench --features parallel for the rayon version
+fn plane_idct(c: &mut Criterion) {
+    use idct_test::plane;
+
+    let (w, h) = (240, 135);
+    let f: Vec<i32> = (0..w * h).flat_map(|_| ZZ).collect();
+    let mut out = vec![0_u8; w * 8 * h * 8];
+    c.bench_function(
+        "LLM IDCT 1080p plane",
+        |b| b.iter(|| plane::idct_plane(black_box(&f), w, h, &mut out, w * 8, idct::llm_idct_plane))
+    );
+    #[cfg(feature = "parallel")]
+    c.bench_function(
+        &format!("LLM IDCT 1080p plane rayon {} threads", rayon::current_num_threads()),
+        |b| b.iter(|| plane::par_idct_plane(black_box(&f), w, h, &mut out, w * 8, idct::llm_idct_plane))
+    );
+}
+
+fn dispatched_idct(c: &mut Criterion) {
+    let mut out = [0_u8;64];
+    let llm = idct::llm_idct_fn();
+    let islow = idct::islow_idct_fn();
+    c.bench_function(
+        &format!("LLM IDCT {:?}", idct::backend()),
+        |b| b.iter(|| llm(black_box(&ZZ), &mut out))
+    );
+    c.bench_function(
+        &format!("ISLOW IDCT {:?}", idct::backend()),
+        |b| b.iter(|| islow(black_box(&ZZ), &mut out))
+    );
+}
+
+// cargo +nightly bench --features portable_simd
+#[cfg(feature = "portable_simd")]
+fn portable_simd(c: &mut Criterion) {
+    use idct_test::portable;
+
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "LLM IDCT portable_simd",
+        |b| b.iter(|| portable::llm_idct_into(black_box(&ZZ), &mut out))
+    );
+    c.bench_function(
+        "AAN IDCT portable_simd",
+        |b| b.iter(|| portable::fast_idct_into(black_box(&ZZ), &mut out))
+    );
+    c.bench_function(
+        "LLM FDCT portable_simd",
+        |b| b.iter(|| portable::llm_fdct(black_box(&Z)))
+    );
+}
+
+#[cfg(not(feature = "portable_simd"))]
+fn portable_simd(_c: &mut Criterion) {}
+
 fn llm_fdct(c: &mut Criterion) {
     c.bench_function(
         "LLM FDCT",
@@ -67,6 +333,99 @@ fn llm_fdct(c: &mut Criterion) {
     );
 }
 
+// a 256x192 4:2:0 file end to end, Huffman decoding included
+fn decode_jpeg(c: &mut Criterion) {
+    let jpeg = include_bytes!("../testdata/bench_420.jpg");
+    c.bench_function(
+        "decode LLM IDCT",
+        |b| b.iter(|| decoder::decode::<LlmIdct>(black_box(jpeg)).unwrap())
+    );
+    c.bench_function(
+        "decode AAN IDCT",
+        |b| b.iter(|| decoder::decode::<AanIdct>(black_box(jpeg)).unwrap())
+    );
+    c.bench_function(
+        "decode ISLOW IDCT",
+        |b| b.iter(|| decoder::decode::<IslowIdct>(black_box(jpeg)).unwrap())
+    );
+    c.bench_function(
+        "decode IFAST IDCT dequant",
+        |b| b.iter(|| decoder::decode_dequant(black_box(jpeg), idct::ifast_prepare_quant,
+            idct::ifast_idct_dequant_plane).unwrap())
+    );
+}
+
+// the decoded bench file encoded again at quality 75, 4:2:0
+fn encode_jpeg(c: &mut Criterion) {
+    let image = decoder::decode::<IslowIdct>(include_bytes!("../testdata/bench_420.jpg")).unwrap();
+    let (pixels, width, height) = (image.pixels(), image.width, image.height);
+    let options = EncodeOptions::default();
+    c.bench_function(
+        "encode LLM FDCT",
+        |b| b.iter(|| encoder::encode::<LlmFdct>(black_box(&pixels), width, height, 3, &options))
+    );
+    c.bench_function(
+        "encode AAN FDCT",
+        |b| b.iter(|| encoder::encode::<AanFdct>(black_box(&pixels), width, height, 3, &options))
+    );
+    c.bench_function(
+        "encode ISLOW FDCT quantize",
+        |b| b.iter(|| encoder::encode_quantize(black_box(&pixels), width, height, 3, &options,
+            fdct::islow_fdct_divisors, fdct::islow_fdct_quantize))
+    );
+    let options = EncodeOptions { optimize_huffman: true, ..options };
+    c.bench_function(
+        "encode ISLOW FDCT optimized Huffman",
+        |b| b.iter(|| encoder::encode_quantize(black_box(&pixels), width, height, 3, &options,
+            fdct::islow_fdct_divisors, fdct::islow_fdct_quantize))
+    );
+}
+
+// Z through LLM FDCT, quality 75 quantization and back through LLM IDCT
+fn quant_round_trip(c: &mut Criterion) {
+    let q = quant::quality_table(&quant::LUMINANCE, 75);
+    let mut out = [0_u8;64];
+    c.bench_function(
+        "LLM FDCT quantize IDCT",
+        |b| b.iter(|| {
+            let coefficients = quant::fdct_quantize::<LlmFdct>(black_box(&Z), &q);
+            quant::dequantize_idct::<LlmIdct>(&coefficients, &q, &mut out)
+        })
+    );
+}
+
+// rate-distortion quantization of the same block
+fn trellis_quantize(c: &mut Criterion) {
+    let q = quant::quality_table(&quant::LUMINANCE, 75);
+    let ac = HuffmanSpec::ac_luminance().code_lengths();
+    let lambda = trellis::default_lambda(&q);
+    c.bench_function(
+        "LLM FDCT trellis quantize",
+        |b| b.iter(|| trellis::fdct_trellis(black_box(&Z), &q, &ac, lambda))
+    );
+}
+
+// FDCT and quantization, the divisor tables built once
+fn aan_fdct_quantize(c: &mut Criterion) {
+    let q = [16_u16;64];
+    let aan = fdct::aan_fdct_divisors(&q);
+    let ifast = fdct::ifast_fdct_divisors(&q);
+    let islow = fdct::islow_fdct_divisors(&q);
+    let mut out = [0_i32;64];
+    c.bench_function(
+        "AAN FDCT quantize",
+        |b| b.iter(|| fdct::aan_fdct_quantize(black_box(&Z), &aan, &mut out))
+    );
+    c.bench_function(
+        "IFAST FDCT quantize",
+        |b| b.iter(|| fdct::ifast_fdct_quantize(black_box(&Z), &ifast, &mut out))
+    );
+    c.bench_function(
+        "ISLOW FDCT quantize",
+        |b| b.iter(|| fdct::islow_fdct_quantize(black_box(&Z), &islow, &mut out))
+    );
+}
+
 fn std_fdct(c: &mut Criterion) {
     c.bench_function(
         "Standard FDCT",
@@ -74,5 +433,8 @@ fn std_fdct(c: &mut Criterion) {
     );
 }
 
-criterion_group!(benches,idct,ap922_idct, aan_idct, aan_idct_f64, llm_idct,std_fdct,llm_fdct);
+criterion_group!(benches,idct,ap922_idct, aan_idct, aan_idct_f64, llm_idct,
+    llm_idct_into, aan_idct_into, llm_idct_dequant, zigzag_idct, aan_idct_dequant, ifast_idct_dequant,
+    idct_4x4, idct_2x2, scaled_idct, sparse_idct, islow_idct, simd_idct,
+    portable_simd, batch_idct, plane_idct, dispatched_idct, decode_jpeg, encode_jpeg, std_fdct,llm_fdct, quant_round_trip, trellis_quantize, aan_fdct_quantize);
 criterion_main!(benches);
\ No newline at end of file
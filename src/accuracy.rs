@@ -0,0 +1,237 @@
+// Accuracy of the IDCTs against a reference, over a block corpus
+//
+// `report::<T>()` compares any `InverseDct` against `idct_f64`,
+// `compare()` takes any two functions or closures so any pair can be
+// measured (e.g. fast_idct against an integer IDCT).
+
+use crate::idct;
+use crate::transform::InverseDct;
+
+pub use crate::idct::IdctFn;
+
+#[derive(Debug, Clone)]
+pub struct Report {
+    pub blocks: usize,
+    pub max_abs_error: i32,
+    pub mse: f64,
+    pub psnr: f64,            // dB, infinity when both outputs are identical
+    pub mean_error: f64,      // signed, shows a rounding bias
+    pub error_map: [f64;64],  // mean absolute error per pixel position
+    pub off_by_one: usize,    // pixels exactly ±1 away from the reference
+    pub mismatched: usize,    // pixels that differ at all
+}
+
+impl Report {
+    pub fn print_map(&self) -> String {
+        let mut str = "".to_string();
+        for row in self.error_map.chunks_exact(8) {
+            str += &format!("{:.3} {:.3} {:.3} {:.3} {:.3} {:.3} {:.3} {:.3}\n",
+                row[0], row[1], row[2], row[3], row[4], row[5], row[6], row[7]);
+        }
+        str
+    }
+}
+
+// compares func against reference block by block
+pub fn compare(
+    reference: impl Fn(&[i32;64], &mut [u8;64]),
+    func: impl Fn(&[i32;64], &mut [u8;64]),
+    corpus: &[[i32;64]],
+) -> Report {
+    let mut max_abs_error = 0;
+    let mut sum = 0_i64;
+    let mut sum2 = 0_i64;
+    let mut map = [0_i64;64];
+    let mut off_by_one = 0;
+    let mut mismatched = 0;
+    let mut want = [0_u8;64];
+    let mut got = [0_u8;64];
+
+    for f in corpus {
+        reference(f, &mut want);
+        func(f, &mut got);
+        for i in 0..64 {
+            let err = got[i] as i32 - want[i] as i32;
+            let abs = err.abs();
+            max_abs_error = max_abs_error.max(abs);
+            sum += err as i64;
+            sum2 += (err * err) as i64;
+            map[i] += abs as i64;
+            if abs == 1 {
+                off_by_one += 1;
+            }
+            if abs != 0 {
+                mismatched += 1;
+            }
+        }
+    }
+
+    let blocks = corpus.len();
+    let pixels = (blocks * 64).max(1) as f64;
+    let mse = sum2 as f64 / pixels;
+    let mut error_map = [0_f64;64];
+    for i in 0..64 {
+        error_map[i] = map[i] as f64 / blocks.max(1) as f64;
+    }
+    Report {
+        blocks,
+        max_abs_error,
+        mse,
+        psnr: 10.0 * (255.0 * 255.0 / mse).log10(),
+        mean_error: sum as f64 / pixels,
+        error_map,
+        off_by_one,
+        mismatched,
+    }
+}
+
+// T against the f64 reference idct_f64
+pub fn report<T: InverseDct>(corpus: &[[i32;64]]) -> Report {
+    compare(idct::idct_f64_into, T::idct_into, corpus)
+}
+
+// random number generator of IEEE Std 1180-1990 Annex A
+pub struct Ieee1180Rand {
+    x: u32,
+}
+
+impl Ieee1180Rand {
+    pub fn new() -> Self {
+        Ieee1180Rand { x: 1 }
+    }
+
+    // uniform in -l..=h
+    pub fn next(&mut self, l: i32, h: i32) -> i32 {
+        self.x = self.x.wrapping_mul(1103515245).wrapping_add(12345);
+        let i = self.x & 0x7ffffffe;
+        let x = i as f64 / 2147483647.0 * (l + h + 1) as f64;
+        x as i32 - l
+    }
+}
+
+impl Default for Ieee1180Rand {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// c[x][u] = C(u) / 2 * cos((2x + 1)uπ / 16)
+pub fn cos_table() -> [[f64;8];8] {
+    let mut c = [[0.0;8];8];
+    for (x, row) in c.iter_mut().enumerate() {
+        for (u, val) in row.iter_mut().enumerate() {
+            let cu = if u == 0 {core::f64::consts::FRAC_1_SQRT_2} else {1.0};
+            *val = cu / 2.0 * ((2 * x + 1) as f64 * u as f64 * core::f64::consts::PI / 16.0).cos();
+        }
+    }
+    c
+}
+
+// double precision FDCT of signed samples, rounded and clipped to -2048..2047
+pub fn reference_fdct(c: &[[f64;8];8], f: &[i32;64]) -> [i32;64] {
+    let mut out = [0;64];
+    for v in 0..8 {
+        for u in 0..8 {
+            let mut val = 0.0;
+            for y in 0..8 {
+                for x in 0..8 {
+                    val += c[x][u] * c[y][v] * f[y * 8 + x] as f64;
+                }
+            }
+            out[v * 8 + u] = (val.round() as i32).clamp(-2048, 2047);
+        }
+    }
+    out
+}
+
+// IEEE 1180 style corpus: random samples in -l..=h (times sign) through
+// the reference FDCT, so the coefficients look like a real encoder's
+pub fn ieee1180_corpus(blocks: usize, l: i32, h: i32, sign: i32) -> Vec<[i32;64]> {
+    let c = cos_table();
+    let mut rand = Ieee1180Rand::new();
+    (0..blocks).map(|_| {
+        let mut block = [0;64];
+        for val in block.iter_mut() {
+            *val = rand.next(l, h) * sign;
+        }
+        reference_fdct(&c, &block)
+    }).collect()
+}
+
+// uniformly random coefficients in -range..=range, the first `nonzero`
+// positions in natural order, the rest zero
+pub fn random_corpus(blocks: usize, range: i32, nonzero: usize) -> Vec<[i32;64]> {
+    let mut rand = Ieee1180Rand::new();
+    (0..blocks).map(|_| {
+        let mut block = [0;64];
+        for val in block.iter_mut().take(nonzero) {
+            *val = rand.next(range, range);
+        }
+        block
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transform::IslowIdct;
+
+    // samples stay near 128, so ±1 never clamps
+    fn small_corpus() -> Vec<[i32;64]> {
+        random_corpus(500, 5, 64)
+    }
+
+    #[test]
+    fn identical_functions() {
+        let r = compare(idct::islow_idct_into, idct::islow_idct_into, &ieee1180_corpus(500, 256, 255, 1));
+        assert_eq!(r.blocks, 500);
+        assert_eq!(r.max_abs_error, 0);
+        assert_eq!(r.mse, 0.0);
+        assert_eq!(r.psnr, f64::INFINITY);
+        assert_eq!(r.mean_error, 0.0);
+        assert_eq!((r.off_by_one, r.mismatched), (0, 0));
+        assert!(r.error_map.iter().all(|v| *v == 0.0));
+    }
+
+    #[test]
+    fn offset_by_one() {
+        let plus = |f: &[i32;64], out: &mut [u8;64]| {
+            idct::islow_idct_into(f, out);
+            out.iter_mut().for_each(|v| *v += 1);
+        };
+        let minus = |f: &[i32;64], out: &mut [u8;64]| {
+            idct::islow_idct_into(f, out);
+            out.iter_mut().for_each(|v| *v -= 1);
+        };
+        let corpus = small_corpus();
+        for (func, sign) in [(plus as IdctFn, 1.0), (minus, -1.0)] {
+            let r = compare(idct::islow_idct_into, func, &corpus);
+            assert_eq!(r.mean_error, sign);
+            assert_eq!(r.max_abs_error, 1);
+            assert_eq!(r.mse, 1.0);
+            assert!((r.psnr - 48.1308).abs() < 1e-4, "psnr {}", r.psnr);
+            assert_eq!(r.off_by_one, corpus.len() * 64);
+            assert_eq!(r.mismatched, corpus.len() * 64);
+            assert!(r.error_map.iter().all(|v| *v == 1.0));
+        }
+    }
+
+    #[test]
+    fn report_is_against_idct_f64() {
+        let corpus = small_corpus();
+        let r = report::<IslowIdct>(&corpus);
+        let want = compare(idct::idct_f64_into, idct::islow_idct_into, &corpus);
+        assert_eq!((r.mse, r.mean_error, r.mismatched), (want.mse, want.mean_error, want.mismatched));
+    }
+
+    // the first outputs of the Annex A C generator, seed 1
+    #[test]
+    fn ieee1180_rand_sequence() {
+        let mut rand = Ieee1180Rand::new();
+        let got: Vec<i32> = (0..16).map(|_| rand.next(256, 255)).collect();
+        assert_eq!(got, [7, -167, -98, 17, 229, -169, 103, -141, -3, -193, -214, -57, -115, -68, 247, 18]);
+        let mut rand = Ieee1180Rand::new();
+        let got: Vec<i32> = (0..16).map(|_| rand.next(5, 5)).collect();
+        assert_eq!(got, [0, -4, -2, 0, 5, -4, 2, -3, 0, -4, -5, -1, -2, -1, 5, 0]);
+    }
+}
@@ -0,0 +1,235 @@
+// Batch IDCTs over a whole component: f is n blocks of 64 coefficients,
+// block b at f[b * 64..][..64] in natural order, vals gets n blocks of 64
+// level shifted samples laid out the same way.
+//
+// llm_idct_blocks and fast_idct_blocks run 8 blocks at a time with block b
+// in lane b of every value, one coefficient position per lane vector. The
+// butterflies (the 1D passes of idct.rs) need no transpose and no
+// shuffles, so the plain [f32;8] arithmetic below vectorizes on any
+// target, and there are 8 independent dependency chains per instruction.
+// Every lane does the same operations in the same order as the single
+// block function, so the output is bit identical to it. The remaining
+// n % 8 blocks go one at a time.
+// On x86_64 the SSE2 / AVX2 kernels of x86.rs are faster than 8 blocks of
+// autovectorized lanes, so llm and islow loop over llm_idct_fn and
+// islow_idct_fn there. The other algorithms loop over the single block
+// function.
+
+use core::ops::{Add, Mul, Sub};
+
+use crate::idct::{self, aan_1d, llm_1d, Lanes};
+use crate::transform::InverseDct;
+
+pub type BlocksFn = fn(&[i32], &mut [u8]);
+
+const LANES: usize = 8;
+const GROUP: usize = 64 * LANES;
+
+// 8 blocks, lane b is block b
+#[derive(Debug, Clone, Copy)]
+struct F8([f32;LANES]);
+
+impl Add for F8 {
+    type Output = Self;
+    #[inline(always)]
+    fn add(self, o: Self) -> Self {
+        F8(core::array::from_fn(|i| self.0[i] + o.0[i]))
+    }
+}
+
+impl Sub for F8 {
+    type Output = Self;
+    #[inline(always)]
+    fn sub(self, o: Self) -> Self {
+        F8(core::array::from_fn(|i| self.0[i] - o.0[i]))
+    }
+}
+
+impl Mul for F8 {
+    type Output = Self;
+    #[inline(always)]
+    fn mul(self, o: Self) -> Self {
+        F8(core::array::from_fn(|i| self.0[i] * o.0[i]))
+    }
+}
+
+impl Lanes for F8 {
+    #[inline(always)]
+    fn splat(x: f32) -> Self {
+        F8([x;LANES])
+    }
+}
+
+fn check_len(f: &[i32], vals: &[u8]) {
+    assert!(f.len().is_multiple_of(64), "coefficients are not whole blocks, len {}", f.len());
+    assert_eq!(f.len(), vals.len(), "coefficient and sample lengths differ");
+}
+
+// any algorithm, one block at a time
+pub fn idct_blocks_with(func: idct::IdctFn, f: &[i32], vals: &mut [u8]) {
+    check_len(f, vals);
+    for (src, dst) in f.chunks_exact(64).zip(vals.chunks_exact_mut(64)) {
+        func(src.try_into().unwrap(), dst.try_into().unwrap());
+    }
+}
+
+// any InverseDct, one block at a time
+pub fn blocks<T: InverseDct>(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(T::idct_into, f, vals);
+}
+
+pub fn idct_blocks(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(idct::idct_into, f, vals);
+}
+
+pub fn idct_f64_blocks(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(idct::idct_f64_into, f, vals);
+}
+
+pub fn fast_idct_f64_blocks(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(idct::fast_idct_f64_into, f, vals);
+}
+
+pub fn ap922_idct_blocks(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(idct::ap922_idct_into, f, vals);
+}
+
+// groups of 8 blocks through kernel, the rest through single
+fn lanes_blocks(f: &[i32], vals: &mut [u8], kernel: fn(&[i32;GROUP], &mut [u8;GROUP]), single: idct::IdctFn) {
+    check_len(f, vals);
+    let whole = f.len() / GROUP * GROUP;
+    for (src, dst) in f[..whole].chunks_exact(GROUP).zip(vals[..whole].chunks_exact_mut(GROUP)) {
+        kernel(src.try_into().unwrap(), dst.try_into().unwrap());
+    }
+    idct_blocks_with(single, &f[whole..], &mut vals[whole..]);
+}
+
+// ws[k] lane b = coefficient k of block b
+#[inline(always)]
+fn load_f8(f: &[i32;GROUP]) -> [F8;64] {
+    core::array::from_fn(|k| F8(core::array::from_fn(|b| f[b * 64 + k] as f32)))
+}
+
+// store_truncate of every lane: level shift, clamp, truncate. Same bytes
+// as `as i32` then clamp, max also turns NaN into 0 (clamp keeps NaN)
+#[inline(always)]
+#[allow(clippy::manual_clamp)]
+fn store_truncate_f8(ws: &[F8;64], vals: &mut [u8;GROUP]) {
+    // SAFETY: the value is in 0.0..=255.0, so the plain cvttps2dq
+    // conversion is exact where `as` would add a saturation check
+    let t: [[i32;LANES];64] = core::array::from_fn(|k| {
+        ws[k].0.map(|x| unsafe { (x + 128.0).max(0.0).min(255.0).to_int_unchecked::<i32>() })
+    });
+    // sample k of block b from lane b of t[k]
+    for (b, dst) in vals.chunks_exact_mut(64).enumerate() {
+        for (d, w) in dst.iter_mut().zip(&t) {
+            *d = w[b] as u8;
+        }
+    }
+}
+
+// 8 blocks of llm_idct_into
+fn llm_idct_8(f: &[i32;GROUP], vals: &mut [u8;GROUP]) {
+    let mut ws = load_f8(f);
+    // rows
+    for row in ws.chunks_exact_mut(8) {
+        let out = llm_1d(core::array::from_fn(|u| row[u]));
+        row.copy_from_slice(&out);
+    }
+    // columns, then √2/4 * √2/4
+    for i in 0..8 {
+        let out = llm_1d(core::array::from_fn(|v| ws[v * 8 + i]));
+        for (v, o) in out.into_iter().enumerate() {
+            ws[v * 8 + i] = o * F8::splat(0.125);
+        }
+    }
+    store_truncate_f8(&ws, vals);
+}
+
+// bit identical to llm_idct_into on every block
+pub fn llm_idct_blocks(f: &[i32], vals: &mut [u8]) {
+    match idct::backend() {
+        // the x86.rs kernels of one block beat 8 blocks in lanes
+        #[cfg(target_arch = "x86_64")]
+        idct::Backend::Sse2 | idct::Backend::Avx2 => idct_blocks_with(idct::llm_idct_fn(), f, vals),
+        _ => lanes_blocks(f, vals, llm_idct_8, idct::llm_idct_into),
+    }
+}
+
+// 8 blocks of fast_idct_into
+fn fast_idct_8(f: &[i32;GROUP], vals: &mut [u8;GROUP]) {
+    let mut ws = load_f8(f);
+    // columns
+    for i in 0..8 {
+        let out = aan_1d::<F8, true>(core::array::from_fn(|v| ws[v * 8 + i]));
+        for (v, o) in out.into_iter().enumerate() {
+            ws[v * 8 + i] = o;
+        }
+    }
+    // rows
+    for row in ws.chunks_exact_mut(8) {
+        let out = aan_1d::<F8, true>(core::array::from_fn(|u| row[u]));
+        row.copy_from_slice(&out);
+    }
+    store_truncate_f8(&ws, vals);
+}
+
+// bit identical to fast_idct_into on every block
+pub fn fast_idct_blocks(f: &[i32], vals: &mut [u8]) {
+    lanes_blocks(f, vals, fast_idct_8, idct::fast_idct_into);
+}
+
+// bit identical to islow_idct_into on every block. No lane kernel: the
+// passes run in i64 like libjpeg's JLONG, the x86.rs kernels and the
+// scalar function are the faster ways there
+pub fn islow_idct_blocks(f: &[i32], vals: &mut [u8]) {
+    idct_blocks_with(idct::islow_idct_fn(), f, vals);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{ieee1180_corpus, random_corpus};
+
+    // 8 * 37 + 5 blocks, so both the lane kernel and the tail run
+    fn coefficients() -> Vec<i32> {
+        let mut corpus = ieee1180_corpus(100, 256, 255, 1);
+        corpus.extend(random_corpus(100, 1000, 3));
+        corpus.extend(random_corpus(101, 2000, 64));
+        corpus.into_iter().flatten().collect()
+    }
+
+    fn assert_batch(batch: &dyn Fn(&[i32], &mut [u8]), single: idct::IdctFn) {
+        let f = coefficients();
+        let mut got = vec![0_u8; f.len()];
+        batch(&f, &mut got);
+        let mut want = [0_u8;64];
+        for (b, (src, dst)) in f.chunks_exact(64).zip(got.chunks_exact(64)).enumerate() {
+            single(src.try_into().unwrap(), &mut want);
+            assert_eq!(&want[..], dst, "block {}", b);
+        }
+    }
+
+    // not through the public functions, x86_64 doesn't use all kernels
+    #[test]
+    fn lane_kernels_match_single_block() {
+        assert_batch(&|f, v| lanes_blocks(f, v, llm_idct_8, idct::llm_idct_into), idct::llm_idct_into);
+        assert_batch(&|f, v| lanes_blocks(f, v, fast_idct_8, idct::fast_idct_into), idct::fast_idct_into);
+    }
+
+    #[test]
+    fn blocks_match_single_block() {
+        assert_batch(&llm_idct_blocks, idct::llm_idct_into);
+        assert_batch(&fast_idct_blocks, idct::fast_idct_into);
+        assert_batch(&islow_idct_blocks, idct::islow_idct_into);
+        assert_batch(&ap922_idct_blocks, idct::ap922_idct_into);
+        assert_batch(&blocks::<crate::transform::LlmIdct>, idct::llm_idct_into);
+    }
+
+    #[test]
+    #[should_panic]
+    fn partial_block_panics() {
+        let mut vals = [0_u8;100];
+        llm_idct_blocks(&[0;100], &mut vals);
+    }
+}
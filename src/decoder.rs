@@ -0,0 +1,745 @@
+// Baseline sequential JPEG decoder (T.81 Annex B and F.2)
+//
+// 8 bit samples, Huffman coding, 1 to 4 components with any sampling
+// factors, restart intervals, interleaved and single component scans
+// (SOF0 and SOF1). Progressive, lossless, hierarchical and arithmetic coded
+// files are rejected with DecodeError::Unsupported.
+//
+// The IDCT is the parameter: decode::<T> for any InverseDct, decode_with
+// for any `*_plane` function of idct.rs, decode_dequant for the IDCTs with
+// the dequantization folded in (`*_prepare_quant` and `*_dequant`), which
+// get the quantized coefficients as libjpeg's do. Blocks are in natural
+// order and written straight into their component plane, so a change of
+// algorithm shows in the decode time and in the decoded samples of real
+// files.
+
+use crate::huffman::{DecodeTable, HuffmanSpec};
+use crate::plane::PlaneFn;
+use crate::transform::InverseDct;
+use crate::zigzag::{to_natural, NATURAL_ORDER};
+use std::fmt;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    // no SOI at the start
+    NotJpeg,
+    // the data ends inside a segment, the entropy coded data or before EOI
+    Truncated,
+    // a valid JPEG this decoder does not handle
+    Unsupported(&'static str),
+    // a malformed segment or entropy coded segment
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::NotJpeg => write!(f, "not a JPEG file"),
+            DecodeError::Truncated => write!(f, "JPEG data is truncated"),
+            DecodeError::Unsupported(what) => write!(f, "unsupported JPEG: {}", what),
+            DecodeError::Invalid(what) => write!(f, "invalid JPEG: {}", what),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+// one decoded component at its own resolution
+#[derive(Debug, Clone)]
+pub struct Component {
+    pub id: u8,
+    // sampling factors
+    pub h: usize,
+    pub v: usize,
+    // samples, ceil(image size * factor / max factor) (A.1.1)
+    pub width: usize,
+    pub height: usize,
+    // data holds whole blocks, row y starts at data[y * stride]
+    pub stride: usize,
+    pub data: Vec<u8>,
+    tq: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Image {
+    pub width: usize,
+    pub height: usize,
+    pub components: Vec<Component>,
+}
+
+impl Image {
+    // interleaved samples: gray for one component, RGB for three (JFIF
+    // YCbCr, chroma replicated like libjpeg without fancy upsampling), the
+    // components as they are otherwise
+    pub fn pixels(&self) -> Vec<u8> {
+        let n = self.components.len();
+        let h_max = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        let v_max = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        let mut out = vec![0_u8; self.width * self.height * n];
+        for (i, c) in self.components.iter().enumerate() {
+            for y in 0..self.height {
+                let row = &c.data[y * c.v / v_max * c.stride..];
+                for x in 0..self.width {
+                    out[(y * self.width + x) * n + i] = row[x * c.h / h_max];
+                }
+            }
+        }
+        if n == 3 {
+            for p in out.chunks_exact_mut(3) {
+                let rgb = ycc_to_rgb(p[0], p[1], p[2]);
+                p.copy_from_slice(&rgb);
+            }
+        }
+        out
+    }
+}
+
+// jdcolor.c ycc_rgb_convert: 16 bit fixed point, same bytes as libjpeg
+fn ycc_to_rgb(y: u8, cb: u8, cr: u8) -> [u8;3] {
+    const ONE_HALF: i32 = 1 << 15;
+    const FIX_1_40200: i32 = 91881;
+    const FIX_1_77200: i32 = 116130;
+    const FIX_0_71414: i32 = 46802;
+    const FIX_0_34414: i32 = 22554;
+    let (y, cb, cr) = (y as i32, cb as i32 - 128, cr as i32 - 128);
+    let r = y + ((FIX_1_40200 * cr + ONE_HALF) >> 16);
+    let g = y + ((-FIX_0_34414 * cb - FIX_0_71414 * cr + ONE_HALF) >> 16);
+    let b = y + ((FIX_1_77200 * cb + ONE_HALF) >> 16);
+    [r.clamp(0, 255) as u8, g.clamp(0, 255) as u8, b.clamp(0, 255) as u8]
+}
+
+pub fn decode<T: InverseDct>(data: &[u8]) -> Result<Image, DecodeError> {
+    decode_with(data, T::idct_plane)
+}
+
+// idct is called with every dequantized block in natural order and writes
+// row y of the block to plane[offset + y * stride..][..8]
+pub fn decode_with(data: &[u8], idct: PlaneFn) -> Result<Image, DecodeError> {
+    decode_dequant(data, |q| *q, |f, q, plane, offset, stride| {
+        // i16 coefficients (see decode_block) times u16 entries fit in i32,
+        // wrapping keeps a crafted file from ever panicking here
+        let f = core::array::from_fn(|i| f[i].wrapping_mul(q[i] as i32));
+        idct(&f, plane, offset, stride)
+    })
+}
+
+// prepare is called once per quantization table (natural order), idct with
+// every quantized block in natural order and the prepared table of its
+// component, e.g. idct::ifast_prepare_quant and idct::ifast_idct_dequant_plane
+pub fn decode_dequant<Q>(
+    data: &[u8],
+    prepare: impl Fn(&[u16;64]) -> Q,
+    idct: impl Fn(&[i32;64], &Q, &mut [u8], usize, usize),
+) -> Result<Image, DecodeError> {
+    let mut decoder = Decoder {
+        data,
+        pos: 0,
+        qt: [None, None, None, None],
+        dc: [None, None, None, None],
+        ac: [None, None, None, None],
+        restart_interval: 0,
+        frame: None,
+        prepare,
+        idct,
+    };
+    decoder.run()
+}
+
+struct Frame {
+    width: usize,
+    height: usize,
+    // MCUs of an interleaved scan
+    mcus_x: usize,
+    mcus_y: usize,
+    components: Vec<Component>,
+}
+
+struct Decoder<'a, Q, P, I> {
+    data: &'a [u8],
+    pos: usize,
+    // prepared tables
+    qt: [Option<Q>;4],
+    dc: [Option<DecodeTable>;4],
+    ac: [Option<DecodeTable>;4],
+    restart_interval: usize,
+    frame: Option<Frame>,
+    prepare: P,
+    idct: I,
+}
+
+impl<'a, Q, P, I> Decoder<'a, Q, P, I>
+where
+    P: Fn(&[u16;64]) -> Q,
+    I: Fn(&[i32;64], &Q, &mut [u8], usize, usize),
+{
+    fn byte(&mut self) -> Result<u8, DecodeError> {
+        let b = *self.data.get(self.pos).ok_or(DecodeError::Truncated)?;
+        self.pos += 1;
+        Ok(b)
+    }
+
+    fn u16(&mut self) -> Result<usize, DecodeError> {
+        Ok((self.byte()? as usize) << 8 | self.byte()? as usize)
+    }
+
+    // the body of a marker segment, after its length field
+    fn segment(&mut self) -> Result<&'a [u8], DecodeError> {
+        let length = self.u16()?;
+        if length < 2 {
+            return Err(DecodeError::Invalid("segment length"));
+        }
+        let start = self.pos;
+        let end = start + length - 2;
+        if end > self.data.len() {
+            return Err(DecodeError::Truncated);
+        }
+        self.pos = end;
+        let data = self.data;
+        Ok(&data[start..end])
+    }
+
+    // next marker code, fill bytes skipped
+    fn marker(&mut self) -> Result<u8, DecodeError> {
+        if self.byte()? != 0xff {
+            return Err(DecodeError::Invalid("marker expected"));
+        }
+        let mut m = self.byte()?;
+        while m == 0xff {
+            m = self.byte()?;
+        }
+        Ok(m)
+    }
+
+    fn run(&mut self) -> Result<Image, DecodeError> {
+        if self.data.len() < 2 || self.data[..2] != [0xff, 0xd8] {
+            return Err(DecodeError::NotJpeg);
+        }
+        self.pos = 2;
+        loop {
+            match self.marker()? {
+                0xd9 => break,
+                0xdb => self.dqt()?,
+                0xc4 => self.dht()?,
+                0xdd => {
+                    let s = self.segment()?;
+                    if s.len() != 2 {
+                        return Err(DecodeError::Invalid("DRI length"));
+                    }
+                    self.restart_interval = (s[0] as usize) << 8 | s[1] as usize;
+                }
+                m @ (0xc0 | 0xc1) => self.sof(m)?,
+                0xc2 | 0xc6 | 0xca | 0xce => return Err(DecodeError::Unsupported("progressive")),
+                0xc3 | 0xc7 | 0xcb | 0xcf => return Err(DecodeError::Unsupported("lossless")),
+                0xc5 | 0xde | 0xdf => return Err(DecodeError::Unsupported("hierarchical")),
+                0xc9 | 0xcc => return Err(DecodeError::Unsupported("arithmetic coding")),
+                0xda => self.sos()?,
+                0xdc => return Err(DecodeError::Unsupported("DNL")),
+                // stray RSTn, SOI, TEM: no segment
+                0xd0..=0xd8 | 0x01 => {}
+                // APPn, COM, JPGn
+                _ => {
+                    self.segment()?;
+                }
+            }
+        }
+        let frame = self.frame.take().ok_or(DecodeError::Invalid("no frame"))?;
+        Ok(Image { width: frame.width, height: frame.height, components: frame.components })
+    }
+
+    fn dqt(&mut self) -> Result<(), DecodeError> {
+        let mut s = self.segment()?;
+        while !s.is_empty() {
+            let (pq, tq) = ((s[0] >> 4) as usize, (s[0] & 15) as usize);
+            let size = if pq == 0 {64} else {128};
+            if pq > 1 || tq > 3 {
+                return Err(DecodeError::Invalid("DQT table"));
+            }
+            if s.len() < 1 + size {
+                return Err(DecodeError::Invalid("DQT length"));
+            }
+            let zz: [u16;64] = core::array::from_fn(|k| {
+                if pq == 0 {s[1 + k] as u16} else {(s[1 + 2 * k] as u16) << 8 | s[2 + 2 * k] as u16}
+            });
+            self.qt[tq] = Some((self.prepare)(&to_natural(&zz)));
+            s = &s[1 + size..];
+        }
+        Ok(())
+    }
+
+    fn dht(&mut self) -> Result<(), DecodeError> {
+        let mut s = self.segment()?;
+        while !s.is_empty() {
+            if s.len() < 17 {
+                return Err(DecodeError::Invalid("DHT length"));
+            }
+            let (tc, th) = (s[0] >> 4, (s[0] & 15) as usize);
+            if tc > 1 || th > 3 {
+                return Err(DecodeError::Invalid("DHT table"));
+            }
+            let bits: [u8;16] = s[1..17].try_into().unwrap();
+            let count: usize = bits.iter().map(|b| *b as usize).sum();
+            if s.len() < 17 + count {
+                return Err(DecodeError::Invalid("DHT length"));
+            }
+            let spec = HuffmanSpec::new(bits, s[17..17 + count].to_vec());
+            let table = DecodeTable::new(&spec).ok_or(DecodeError::Invalid("Huffman table"))?;
+            if tc == 0 {
+                self.dc[th] = Some(table);
+            } else {
+                self.ac[th] = Some(table);
+            }
+            s = &s[17 + count..];
+        }
+        Ok(())
+    }
+
+    fn sof(&mut self, marker: u8) -> Result<(), DecodeError> {
+        if self.frame.is_some() {
+            return Err(DecodeError::Unsupported("more than one frame"));
+        }
+        let s = self.segment()?;
+        if s.len() < 6 {
+            return Err(DecodeError::Invalid("SOF length"));
+        }
+        if s[0] != 8 {
+            return Err(DecodeError::Unsupported(if marker == 0xc0 {"baseline precision"} else {"12 bit samples"}));
+        }
+        let height = (s[1] as usize) << 8 | s[2] as usize;
+        let width = (s[3] as usize) << 8 | s[4] as usize;
+        let n = s[5] as usize;
+        if height == 0 {
+            return Err(DecodeError::Unsupported("DNL"));
+        }
+        if width == 0 || !(1..=4).contains(&n) || s.len() != 6 + 3 * n {
+            return Err(DecodeError::Invalid("SOF"));
+        }
+        let mut components = Vec::with_capacity(n);
+        for c in s[6..].chunks_exact(3) {
+            let (h, v, tq) = ((c[1] >> 4) as usize, (c[1] & 15) as usize, c[2] as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || tq > 3 {
+                return Err(DecodeError::Invalid("SOF component"));
+            }
+            components.push(Component { id: c[0], h, v, width: 0, height: 0, stride: 0, data: Vec::new(), tq });
+        }
+        let h_max = components.iter().map(|c| c.h).max().unwrap();
+        let v_max = components.iter().map(|c| c.v).max().unwrap();
+        let mcus_x = width.div_ceil(8 * h_max);
+        let mcus_y = height.div_ceil(8 * v_max);
+        for c in &mut components {
+            c.width = (width * c.h).div_ceil(h_max);
+            c.height = (height * c.v).div_ceil(v_max);
+            // whole MCUs, the blocks of a single component scan fit in them
+            c.stride = mcus_x * c.h * 8;
+            c.data = vec![0; c.stride * mcus_y * c.v * 8];
+        }
+        self.frame = Some(Frame { width, height, mcus_x, mcus_y, components });
+        Ok(())
+    }
+
+    fn sos(&mut self) -> Result<(), DecodeError> {
+        let s = self.segment()?;
+        let frame = self.frame.as_mut().ok_or(DecodeError::Invalid("SOS before SOF"))?;
+        let n = *s.first().ok_or(DecodeError::Invalid("SOS length"))? as usize;
+        if !(1..=4).contains(&n) || s.len() != 4 + 2 * n {
+            return Err(DecodeError::Invalid("SOS length"));
+        }
+        if s[1 + 2 * n..] != [0, 63, 0] {
+            return Err(DecodeError::Invalid("SOS spectral selection"));
+        }
+        // index into frame.components, DC and AC table
+        let mut scan = Vec::with_capacity(n);
+        for c in s[1..1 + 2 * n].chunks_exact(2) {
+            let index = frame.components.iter().position(|fc| fc.id == c[0])
+                .ok_or(DecodeError::Invalid("SOS component"))?;
+            let (td, ta) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            let dc = self.dc.get(td).and_then(|t| t.as_ref()).ok_or(DecodeError::Invalid("missing DC table"))?;
+            let ac = self.ac.get(ta).and_then(|t| t.as_ref()).ok_or(DecodeError::Invalid("missing AC table"))?;
+            let q = self.qt[frame.components[index].tq].as_ref().ok_or(DecodeError::Invalid("missing quantization table"))?;
+            scan.push(ScanComponent { index, dc, ac, q, pred: 0 });
+        }
+
+        // blocks of every MCU: component of the scan, block column and row
+        // in units of the MCU's own blocks
+        let (mcus_x, mcus_y, layout) = if n == 1 {
+            let c = &frame.components[scan[0].index];
+            (c.width.div_ceil(8), c.height.div_ceil(8), vec![(0, 0, 0)])
+        } else {
+            let mut layout = Vec::new();
+            for (i, sc) in scan.iter().enumerate() {
+                let c = &frame.components[sc.index];
+                for by in 0..c.v {
+                    for bx in 0..c.h {
+                        layout.push((i, bx, by));
+                    }
+                }
+            }
+            if layout.len() > 10 {
+                return Err(DecodeError::Invalid("more than 10 blocks in an MCU"));
+            }
+            (frame.mcus_x, frame.mcus_y, layout)
+        };
+
+        let mut bits = BitReader::new(&self.data[self.pos..]);
+        let mut f = [0_i32;64];
+        let total = mcus_x * mcus_y;
+        for mcu in 0..total {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                let expected = 0xd0 + ((mcu / self.restart_interval - 1) % 8) as u8;
+                bits.restart(expected)?;
+                for sc in &mut scan {
+                    sc.pred = 0;
+                }
+            }
+            let (mx, my) = (mcu % mcus_x, mcu / mcus_x);
+            for (i, bx, by) in &layout {
+                let sc = &mut scan[*i];
+                decode_block(&mut bits, sc, &mut f)?;
+                let c = &mut frame.components[sc.index];
+                let (x, y) = if n == 1 {(mx * 8, my * 8)} else {((mx * c.h + bx) * 8, (my * c.v + by) * 8)};
+                (self.idct)(&f, sc.q, &mut c.data, y * c.stride + x, c.stride);
+            }
+        }
+        if bits.past_end {
+            return Err(DecodeError::Truncated);
+        }
+        // the marker after the entropy coded data
+        self.pos += bits.marker_position();
+        Ok(())
+    }
+}
+
+struct ScanComponent<'a, Q> {
+    index: usize,
+    dc: &'a DecodeTable,
+    ac: &'a DecodeTable,
+    q: &'a Q,
+    pred: i32,
+}
+
+// F.2.2.1 EXTEND: s bits v as a signed value of category s
+#[inline]
+fn extend(v: u32, s: u32) -> i32 {
+    if v < 1 << (s - 1) {v as i32 - (1 << s) + 1} else {v as i32}
+}
+
+// one block of quantized coefficients (F.2.2) in natural order
+fn decode_block<Q>(bits: &mut BitReader, sc: &mut ScanComponent<Q>, f: &mut [i32;64]) -> Result<(), DecodeError> {
+    f.fill(0);
+    let t = bits.decode(sc.dc)?;
+    if t > 11 {
+        return Err(DecodeError::Invalid("DC category"));
+    }
+    if t > 0 {
+        let diff = extend(bits.receive(t), t);
+        sc.pred = sc.pred.checked_add(diff).ok_or(DecodeError::Invalid("DC coefficient"))?;
+    }
+    // stored as a 16 bit JCOEF like libjpeg
+    f[0] = sc.pred as i16 as i32;
+    let mut k = 1;
+    while k < 64 {
+        let rs = bits.decode(sc.ac)?;
+        let (r, s) = (rs >> 4, rs & 15);
+        if s > 10 {
+            // 8 bit samples: AC coefficients are below 2^10
+            return Err(DecodeError::Invalid("AC category"));
+        }
+        if s == 0 {
+            if r != 15 {
+                break; // EOB
+            }
+            k += 16; // ZRL
+            continue;
+        }
+        k += r as usize;
+        if k > 63 {
+            return Err(DecodeError::Invalid("AC coefficient index"));
+        }
+        f[NATURAL_ORDER[k]] = extend(bits.receive(s), s);
+        k += 1;
+    }
+    Ok(())
+}
+
+// entropy coded data reader: 0xff 0x00 is a 0xff byte, a marker ends the
+// data and zeros are read after it (as libjpeg does)
+struct BitReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+    // bits at the top, count of them
+    acc: u64,
+    count: u32,
+    at_marker: bool,
+    // zeros were read after the end of the data, no marker found
+    past_end: bool,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        BitReader { data, pos: 0, acc: 0, count: 0, at_marker: false, past_end: false }
+    }
+
+    #[inline]
+    fn fill(&mut self) {
+        while self.count <= 56 {
+            let mut byte = 0;
+            if self.at_marker {
+                // zeros
+            } else if self.pos >= self.data.len() {
+                self.past_end = true;
+            } else if self.data[self.pos] != 0xff {
+                byte = self.data[self.pos];
+                self.pos += 1;
+            } else if self.data.get(self.pos + 1) == Some(&0) {
+                byte = 0xff;
+                self.pos += 2;
+            } else {
+                self.at_marker = true;
+            }
+            self.acc |= (byte as u64) << (56 - self.count);
+            self.count += 8;
+        }
+    }
+
+    #[inline]
+    fn decode(&mut self, table: &DecodeTable) -> Result<u32, DecodeError> {
+        if self.count < 16 {
+            self.fill();
+        }
+        let (v, length) = table.decode((self.acc >> 48) as u32).ok_or(DecodeError::Invalid("Huffman code"))?;
+        self.acc <<= length;
+        self.count -= length;
+        Ok(v as u32)
+    }
+
+    // F.2.2.4 RECEIVE, s in 1..=16
+    #[inline]
+    fn receive(&mut self, s: u32) -> u32 {
+        if self.count < s {
+            self.fill();
+        }
+        let v = (self.acc >> (64 - s)) as u32;
+        self.acc <<= s;
+        self.count -= s;
+        v
+    }
+
+    // byte offset of the marker that ended the data (the bits read ahead
+    // are whole bytes after it)
+    fn marker_position(&self) -> usize {
+        let mut pos = self.pos;
+        // only padding is left before the marker, skipped with any stuffed 0xff
+        while pos < self.data.len() && (self.data[pos] != 0xff || self.data.get(pos + 1) == Some(&0)) {
+            pos += 1;
+        }
+        pos
+    }
+
+    // RSTn: drop the bits left in the byte, expect the marker
+    fn restart(&mut self, expected: u8) -> Result<(), DecodeError> {
+        let pos = self.marker_position();
+        if self.data.get(pos + 1) != Some(&expected) {
+            return Err(if pos + 1 >= self.data.len() {DecodeError::Truncated} else {DecodeError::Invalid("restart marker")});
+        }
+        self.pos = pos + 2;
+        self.acc = 0;
+        self.count = 0;
+        self.at_marker = false;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idct;
+    use crate::transform::{AanIdct, Ap922Idct, IslowIdct, LlmIdct};
+
+    // made with libjpeg-turbo, .raw is its output with JDCT_ISLOW and
+    // do_fancy_upsampling off (RGB for the color files)
+    const FILES: [(&str, &[u8], &[u8]);4] = [
+        ("gray", include_bytes!("../testdata/gray.jpg"), include_bytes!("../testdata/gray.raw")),
+        // 4:2:0, restart interval 2 MCUs
+        ("color_420", include_bytes!("../testdata/color_420.jpg"), include_bytes!("../testdata/color_420.raw")),
+        // 4:2:2, one scan per component
+        ("color_422_scans", include_bytes!("../testdata/color_422_scans.jpg"), include_bytes!("../testdata/color_422_scans.raw")),
+        // 4:4:4, quality 3: 16 bit DQT in an SOF1 file
+        ("color_444_q16", include_bytes!("../testdata/color_444_q16.jpg"), include_bytes!("../testdata/color_444_q16.raw")),
+    ];
+
+    #[test]
+    fn islow_matches_libjpeg() {
+        for (name, jpeg, want) in FILES {
+            let image = decode::<IslowIdct>(jpeg).unwrap();
+            assert_eq!(image.pixels(), want, "{}", name);
+        }
+    }
+
+    #[test]
+    fn sizes() {
+        let image = decode::<IslowIdct>(FILES[1].1).unwrap();
+        assert_eq!((image.width, image.height), (45, 37));
+        let sizes: Vec<_> = image.components.iter().map(|c| (c.h, c.v, c.width, c.height, c.stride)).collect();
+        assert_eq!(sizes, [(2, 2, 45, 37, 48), (1, 1, 23, 19, 24), (1, 1, 23, 19, 24)]);
+    }
+
+    fn psnr(a: &[u8], b: &[u8]) -> f64 {
+        let sse: f64 = a.iter().zip(b).map(|(a, b)| (*a as f64 - *b as f64).powi(2)).sum();
+        10.0 * (255.0 * 255.0 * a.len() as f64 / sse.max(1e-9)).log10()
+    }
+
+    // llm_idct_dequant through decode_dequant
+    fn llm_dequant(f: &[i32;64], table: &[f32;64], plane: &mut [u8], offset: usize, stride: usize) {
+        let mut out = [0_u8;64];
+        idct::llm_idct_dequant(f, table, &mut out);
+        for (y, row) in out.chunks_exact(8).enumerate() {
+            plane[offset + y * stride..][..8].copy_from_slice(row);
+        }
+    }
+
+    // every algorithm decodes the same files, close to libjpeg
+    #[test]
+    fn pluggable_idct() {
+        for (name, jpeg, want) in FILES {
+            let results = [
+                ("llm", decode::<LlmIdct>(jpeg), 44.0),
+                ("aan", decode::<AanIdct>(jpeg), 44.0),
+                ("ap922", decode::<Ap922Idct>(jpeg), 55.0),
+                ("fast plane", decode_with(jpeg, idct::fast_idct_plane), 44.0),
+                ("llm dequant", decode_dequant(jpeg, idct::llm_prepare_quant, llm_dequant), 44.0),
+                ("ifast dequant", decode_dequant(jpeg, idct::ifast_prepare_quant, idct::ifast_idct_dequant_plane), 44.0),
+            ];
+            for (algorithm, image, min) in results {
+                let db = psnr(&image.unwrap().pixels(), want);
+                assert!(db > min, "{} {}: {:.1} dB", name, algorithm, db);
+            }
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let jpeg = FILES[1].1;
+        assert_eq!(decode::<LlmIdct>(&[0, 1, 2]).unwrap_err(), DecodeError::NotJpeg);
+        // cut everywhere: an error, never a panic
+        for end in 2..jpeg.len() - 2 {
+            assert!(decode::<LlmIdct>(&jpeg[..end]).is_err(), "cut at {}", end);
+        }
+        assert_eq!(decode::<LlmIdct>(&jpeg[..jpeg.len() - 2]).unwrap_err(), DecodeError::Truncated);
+
+        // SOF0 patched to SOF2
+        let sof = jpeg.windows(2).position(|w| w == [0xff, 0xc0]).unwrap();
+        let mut progressive = jpeg.to_vec();
+        progressive[sof + 1] = 0xc2;
+        assert_eq!(decode::<LlmIdct>(&progressive).unwrap_err(), DecodeError::Unsupported("progressive"));
+
+        // a wrong restart marker
+        let rst = jpeg.windows(2).position(|w| w == [0xff, 0xd0]).unwrap();
+        let mut wrong = jpeg.to_vec();
+        wrong[rst + 1] = 0xd3;
+        assert_eq!(decode::<LlmIdct>(&wrong).unwrap_err(), DecodeError::Invalid("restart marker"));
+
+        // corrupt entropy coded data decodes to something or fails cleanly
+        let sos = jpeg.windows(2).position(|w| w == [0xff, 0xda]).unwrap();
+        for i in (sos + 14..jpeg.len() - 2).step_by(7) {
+            let mut corrupt = jpeg.to_vec();
+            corrupt[i] ^= 0x5a;
+            let _ = decode::<LlmIdct>(&corrupt);
+        }
+
+        // AC symbol 0x01 (run 0, one bit) patched to category 11
+        let mut jpeg = FILES[0].1.to_vec();
+        let dht = (0..jpeg.len() - 1)
+            .find(|&i| jpeg[i] == 0xff && jpeg[i + 1] == 0xc4 && jpeg[i + 4] >> 4 == 1)
+            .unwrap();
+        let count: usize = jpeg[dht + 5..dht + 21].iter().map(|b| *b as usize).sum();
+        let values = dht + 21..dht + 21 + count;
+        let one = values.clone().find(|&i| jpeg[i] == 0x01).unwrap();
+        assert!(!jpeg[values].contains(&0x0b));
+        jpeg[one] = 0x0b;
+        assert_eq!(decode::<LlmIdct>(&jpeg).unwrap_err(), DecodeError::Invalid("AC category"));
+    }
+
+    // every algorithm on a file, Ok or an error but no panic
+    fn decode_all(jpeg: &[u8]) -> Vec<Result<Image, DecodeError>> {
+        vec![
+            decode::<IslowIdct>(jpeg),
+            decode::<LlmIdct>(jpeg),
+            decode::<AanIdct>(jpeg),
+            decode::<Ap922Idct>(jpeg),
+            decode_with(jpeg, idct::fast_idct_plane),
+            decode_dequant(jpeg, idct::llm_prepare_quant, llm_dequant),
+            decode_dequant(jpeg, idct::ifast_prepare_quant, idct::ifast_idct_dequant_plane),
+        ]
+    }
+
+    // 16 bit DQT entries of 65535: dequantized coefficients far beyond
+    // anything 8 bit samples give, also with corrupt entropy coded data
+    #[test]
+    fn sixteen_bit_tables() {
+        let mut jpeg = FILES[3].1.to_vec();
+        let mut patched = 0;
+        let mut i = 0;
+        while i + 4 < jpeg.len() && !(jpeg[i] == 0xff && jpeg[i + 1] == 0xda) {
+            if jpeg[i] == 0xff && jpeg[i + 1] == 0xdb {
+                let len = (jpeg[i + 2] as usize) << 8 | jpeg[i + 3] as usize;
+                let mut t = i + 4;
+                while t < i + 2 + len {
+                    assert_eq!(jpeg[t] >> 4, 1, "8 bit table");
+                    jpeg[t + 1..t + 129].fill(0xff);
+                    t += 129;
+                    patched += 1;
+                }
+                i += 2 + len;
+            } else {
+                i += 1;
+            }
+        }
+        assert!(patched > 0);
+        for image in decode_all(&jpeg) {
+            assert!(image.is_ok());
+        }
+        let sos = jpeg.windows(2).position(|w| w == [0xff, 0xda]).unwrap();
+        for i in (sos + 14..jpeg.len() - 2).step_by(5) {
+            let mut corrupt = jpeg.to_vec();
+            corrupt[i] ^= 0xa5;
+            decode_all(&corrupt);
+        }
+
+        // DC categories 0 and 11 swapped: large differences pile up in the
+        // predictor and the coefficients leave 16 bits
+        let mut i = 0;
+        while let Some(p) = jpeg[i..].windows(2).position(|w| w == [0xff, 0xc4]) {
+            let dht = i + p;
+            if jpeg[dht + 4] >> 4 == 0 {
+                let count: usize = jpeg[dht + 5..dht + 21].iter().map(|b| *b as usize).sum();
+                for v in &mut jpeg[dht + 21..dht + 21 + count] {
+                    *v = match *v { 0 => 11, 11 => 0, v => v };
+                }
+            }
+            i = dht + 2;
+        }
+        decode_all(&jpeg);
+        for i in (sos + 14..jpeg.len() - 2).step_by(5) {
+            let mut corrupt = jpeg.to_vec();
+            corrupt[i] ^= 0xa5;
+            decode_all(&corrupt);
+        }
+    }
+
+    // DC differences add up to i32::MAX at most, the block gets the 16 bit
+    // JCOEF of the predictor
+    #[test]
+    fn dc_predictor() {
+        // DC: '0' is category 11, AC: '0' is EOB
+        let dc = DecodeTable::new(&HuffmanSpec::new([2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], vec![11, 0])).unwrap();
+        let ac = DecodeTable::new(&HuffmanSpec::new([1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0], vec![0])).unwrap();
+        let q = [1_u16;64];
+        // +2047 then EOB: 0 11111111111 0
+        let data = [0x7f, 0xf0];
+        let mut f = [0;64];
+        for (pred, want) in [(0, Ok(2047)), (32767, Ok(-30722)), (i32::MAX - 2046, Err(DecodeError::Invalid("DC coefficient")))] {
+            let mut sc = ScanComponent { index: 0, dc: &dc, ac: &ac, q: &q, pred };
+            let got = decode_block(&mut BitReader::new(&data), &mut sc, &mut f).map(|_| f[0]);
+            assert_eq!(got, want, "predictor {}", pred);
+        }
+    }
+}
@@ -0,0 +1,519 @@
+// Baseline sequential JPEG encoder, JFIF output (T.81 Annex B and F.1)
+//
+// 8 bit grayscale or RGB input. RGB is converted to YCbCr and the chroma
+// subsampled like libjpeg (jccolor.c rgb_ycc_convert, jcsample.c without
+// smoothing), the image padded to whole MCUs by repeating the last column
+// and row. Quantization tables are the Annex K ones scaled for the quality
+// (quant::quality_table), Huffman tables the Annex K ones or optimal ones
+// for the image (HuffmanSpec::optimal, a second pass over the coefficients
+// like cjpeg -optimize). One interleaved scan, no restart intervals.
+//
+// The FDCT is the parameter, like the IDCT of decoder.rs: encode::<T> for
+// any ForwardDct, encode_quantize for the FDCTs with the quantization
+// folded in (`*_fdct_divisors` and `*_fdct_quantize`). Blocks are
+// quantized in natural order. With fdct::islow_fdct_quantize the output is
+// byte for byte that of libjpeg with JDCT_ISLOW.
+
+use crate::huffman::{category, HuffmanSpec};
+use crate::quant::{self, CHROMINANCE, LUMINANCE};
+use crate::transform::ForwardDct;
+use crate::zigzag::{to_zigzag, NATURAL_ORDER};
+
+// sampling of the chroma components, luma factors 1x1, 2x1 and 2x2
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Subsampling {
+    S444,
+    S422,
+    S420,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EncodeOptions {
+    // 1..100, IJG scaling of the Annex K tables
+    pub quality: u32,
+    // ignored for grayscale input
+    pub subsampling: Subsampling,
+    // optimal Huffman tables instead of the Annex K ones
+    pub optimize_huffman: bool,
+}
+
+impl Default for EncodeOptions {
+    // cjpeg defaults
+    fn default() -> Self {
+        EncodeOptions { quality: 75, subsampling: Subsampling::S420, optimize_huffman: false }
+    }
+}
+
+// pixels is width * height samples, interleaved RGB for 3 components
+pub fn encode<T: ForwardDct>(pixels: &[u8], width: usize, height: usize, components: usize,
+    options: &EncodeOptions) -> Vec<u8> {
+    encode_quantize(pixels, width, height, components, options, |q| *q, |f, q, out| {
+        *out = quant::fdct_quantize::<T>(f, q);
+    })
+}
+
+// prepare is called once per quantization table (natural order), quantize
+// with every block of samples and the prepared table of its component and
+// writes the quantized coefficients in natural order, e.g.
+// fdct::islow_fdct_divisors and fdct::islow_fdct_quantize
+pub fn encode_quantize<Q>(
+    pixels: &[u8],
+    width: usize,
+    height: usize,
+    components: usize,
+    options: &EncodeOptions,
+    prepare: impl Fn(&[u16;64]) -> Q,
+    quantize: impl Fn(&[u8;64], &Q, &mut [i32;64]),
+) -> Vec<u8> {
+    assert!(components == 1 || components == 3, "{} components, 1 or 3 supported", components);
+    assert!((1..=65535).contains(&width) && (1..=65535).contains(&height), "size {}x{}", width, height);
+    assert_eq!(pixels.len(), width * height * components, "pixels for {}x{}x{}", width, height, components);
+
+    // table 0 luma, 1 chroma
+    let tables = [
+        quant::quality_table(&LUMINANCE, options.quality),
+        quant::quality_table(&CHROMINANCE, options.quality),
+    ];
+    let prepared = [prepare(&tables[0]), prepare(&tables[1])];
+
+    let (h_max, v_max) = match (components, options.subsampling) {
+        (1, _) | (_, Subsampling::S444) => (1, 1),
+        (_, Subsampling::S422) => (2, 1),
+        (_, Subsampling::S420) => (2, 2),
+    };
+    let mcus_x = width.div_ceil(8 * h_max);
+    let mcus_y = height.div_ceil(8 * v_max);
+    let planes = planes(pixels, width, height, components, mcus_x * 8 * h_max, mcus_y * 8 * v_max);
+
+    let mut coded = Vec::with_capacity(components);
+    for (i, plane) in planes.iter().enumerate() {
+        let (h, v) = if i == 0 { (h_max, v_max) } else { (1, 1) };
+        let plane = match (h_max / h, v_max / v) {
+            (1, 1) => plane.clone(),
+            (2, 1) => downsample_h2v1(plane, mcus_x * 8 * h_max),
+            _ => downsample_h2v2(plane, mcus_x * 8 * h_max),
+        };
+        let table = i.min(1);
+        let blocks = blocks(&plane, mcus_x * 8 * h, mcus_y * 8 * v, &prepared[table], &quantize);
+        let mut component = Coded { h, v, table, blocks_x: mcus_x * h, blocks };
+        // blocks of the component itself, ceil(image size * factor / max
+        // factor) samples (A.1.1)
+        let blocks_x = (width * h).div_ceil(h_max).div_ceil(8);
+        let blocks_y = (height * v).div_ceil(v_max).div_ceil(8);
+        component.dummy_blocks(blocks_x, blocks_y);
+        coded.push(component);
+    }
+
+    let mut dc = [HuffmanSpec::dc_luminance(), HuffmanSpec::dc_chrominance()];
+    let mut ac = [HuffmanSpec::ac_luminance(), HuffmanSpec::ac_chrominance()];
+    if options.optimize_huffman {
+        let mut freq = [[[0_u32;256];2];2];
+        scan(&coded, mcus_x, mcus_y, |c, is_ac, symbol, _, _| {
+            freq[is_ac as usize][coded[c].table][symbol as usize] += 1;
+        });
+        // tables no component uses stay Annex K, they are not written
+        for table in 0..coded.len().min(2) {
+            dc[table] = HuffmanSpec::optimal(&freq[0][table]);
+            ac[table] = HuffmanSpec::optimal(&freq[1][table]);
+        }
+    }
+
+    let tables = &tables[..coded.len().min(2)];
+    let mut out = Vec::new();
+    write_headers(&mut out, width, height, &coded, tables, &dc, &ac);
+
+    let codes = [dc.each_ref().map(|s| s.codes().unwrap()), ac.each_ref().map(|s| s.codes().unwrap())];
+    let mut writer = BitWriter { out, bits: 0, count: 0 };
+    scan(&coded, mcus_x, mcus_y, |c, is_ac, symbol, extra, size| {
+        let (code, length) = codes[is_ac as usize][coded[c].table][symbol as usize];
+        writer.put(code as u32, length as u32);
+        writer.put(extra, size as u32);
+    });
+    let mut out = writer.finish();
+    out.extend_from_slice(&[0xff, 0xd9]);
+    out
+}
+
+// one component, quantized blocks in raster order over the padded plane
+struct Coded {
+    h: usize,
+    v: usize,
+    table: usize,
+    blocks_x: usize,
+    blocks: Vec<[i32;64]>,
+}
+
+impl Coded {
+    // the blocks of the last MCU column and row past the component's own
+    // blocks are never decoded: the DC of the block before them in the MCU,
+    // no AC, so they cost a few bits (jccoefct.c compress_data)
+    fn dummy_blocks(&mut self, blocks_x: usize, blocks_y: usize) {
+        let rows = self.blocks.len() / self.blocks_x;
+        for row in 0..rows {
+            for col in 0..self.blocks_x {
+                let before = if row >= blocks_y {
+                    // last block of the row above in the same MCU
+                    (row - 1) * self.blocks_x + (col / self.h + 1) * self.h - 1
+                } else if col >= blocks_x {
+                    row * self.blocks_x + col - 1
+                } else {
+                    continue;
+                };
+                let dc = self.blocks[before][0];
+                self.blocks[row * self.blocks_x + col] = [0;64];
+                self.blocks[row * self.blocks_x + col][0] = dc;
+            }
+        }
+    }
+}
+
+// jccolor.c rgb_ycc_convert: 16 bit fixed point, Cb and Cr rounded so
+// they stay below 256
+fn rgb_to_ycc(r: u8, g: u8, b: u8) -> [u8;3] {
+    const ONE_HALF: i32 = 1 << 15;
+    const CBCR_OFFSET: i32 = 128 << 16;
+    const FIX_0_29900: i32 = 19595;
+    const FIX_0_58700: i32 = 38470;
+    const FIX_0_11400: i32 = 7471;
+    const FIX_0_16874: i32 = 11059;
+    const FIX_0_33126: i32 = 21709;
+    const FIX_0_50000: i32 = 32768;
+    const FIX_0_41869: i32 = 27439;
+    const FIX_0_08131: i32 = 5329;
+    let (r, g, b) = (r as i32, g as i32, b as i32);
+    let y = (FIX_0_29900 * r + FIX_0_58700 * g + FIX_0_11400 * b + ONE_HALF) >> 16;
+    let cb = (-FIX_0_16874 * r - FIX_0_33126 * g + FIX_0_50000 * b + CBCR_OFFSET + ONE_HALF - 1) >> 16;
+    let cr = (FIX_0_50000 * r - FIX_0_41869 * g - FIX_0_08131 * b + CBCR_OFFSET + ONE_HALF - 1) >> 16;
+    [y as u8, cb as u8, cr as u8]
+}
+
+// full resolution component planes of padded_width x padded_height, the
+// last column and row repeated
+fn planes(pixels: &[u8], width: usize, height: usize, components: usize,
+    padded_width: usize, padded_height: usize) -> Vec<Vec<u8>> {
+    let mut planes = vec![vec![0_u8; padded_width * padded_height]; components];
+    for y in 0..padded_height {
+        let row = &pixels[y.min(height - 1) * width * components..][..width * components];
+        for x in 0..padded_width {
+            let p = &row[x.min(width - 1) * components..][..components];
+            let samples = if components == 3 { rgb_to_ycc(p[0], p[1], p[2]) } else { [p[0], 0, 0] };
+            for (plane, sample) in planes.iter_mut().zip(samples) {
+                plane[y * padded_width + x] = sample;
+            }
+        }
+    }
+    planes
+}
+
+// jcsample.c h2v1_downsample: pairs averaged, the rounding bias
+// alternating 0, 1 so it does not drift
+fn downsample_h2v1(plane: &[u8], width: usize) -> Vec<u8> {
+    plane.chunks_exact(width).flat_map(|row| {
+        row.chunks_exact(2).enumerate().map(|(x, p)| ((p[0] as u32 + p[1] as u32 + (x as u32 & 1)) >> 1) as u8)
+    }).collect()
+}
+
+// jcsample.c h2v2_downsample: 2x2 averaged, bias 1, 2
+fn downsample_h2v2(plane: &[u8], width: usize) -> Vec<u8> {
+    plane.chunks_exact(2 * width).flat_map(|rows| {
+        let (a, b) = rows.split_at(width);
+        (0..width / 2).map(move |x| {
+            let sum = a[2 * x] as u32 + a[2 * x + 1] as u32 + b[2 * x] as u32 + b[2 * x + 1] as u32;
+            ((sum + 1 + (x as u32 & 1)) >> 2) as u8
+        })
+    }).collect()
+}
+
+fn blocks<Q>(plane: &[u8], width: usize, height: usize, q: &Q,
+    quantize: &impl Fn(&[u8;64], &Q, &mut [i32;64])) -> Vec<[i32;64]> {
+    let mut blocks = Vec::with_capacity(width * height / 64);
+    let mut samples = [0_u8;64];
+    for by in (0..height).step_by(8) {
+        for bx in (0..width).step_by(8) {
+            for (y, row) in samples.chunks_exact_mut(8).enumerate() {
+                row.copy_from_slice(&plane[(by + y) * width + bx..][..8]);
+            }
+            let mut block = [0_i32;64];
+            quantize(&samples, q, &mut block);
+            blocks.push(block);
+        }
+    }
+    blocks
+}
+
+// walks the scan in MCU order, emit gets every symbol with its component,
+// whether it is an AC one, and the extra bits with their count
+fn scan(coded: &[Coded], mcus_x: usize, mcus_y: usize, mut emit: impl FnMut(usize, bool, u8, u32, u8)) {
+    let mut last_dc = vec![0_i32; coded.len()];
+    for my in 0..mcus_y {
+        for mx in 0..mcus_x {
+            for (c, component) in coded.iter().enumerate() {
+                for v in 0..component.v {
+                    for h in 0..component.h {
+                        let row = my * component.v + v;
+                        let col = mx * component.h + h;
+                        let block = &component.blocks[row * component.blocks_x + col];
+                        encode_block(block, &mut last_dc[c], |is_ac, symbol, extra, size| {
+                            emit(c, is_ac, symbol, extra, size);
+                        });
+                    }
+                }
+            }
+        }
+    }
+}
+
+// F.1.2: DC difference category, then RRRRSSSS symbols in zigzag order
+// with ZRL for runs over 15 and EOB after the last non zero coefficient
+fn encode_block(f: &[i32;64], last_dc: &mut i32, mut emit: impl FnMut(bool, u8, u32, u8)) {
+    let diff = f[0] - *last_dc;
+    *last_dc = f[0];
+    let size = category(diff);
+    emit(false, size, extra_bits(diff, size), size);
+
+    let mut run = 0;
+    for i in NATURAL_ORDER.iter().skip(1) {
+        let v = f[*i];
+        if v == 0 {
+            run += 1;
+            continue;
+        }
+        while run > 15 {
+            emit(true, 0xf0, 0, 0);
+            run -= 16;
+        }
+        let size = category(v);
+        emit(true, (run << 4) as u8 | size, extra_bits(v, size), size);
+        run = 0;
+    }
+    if run > 0 {
+        emit(true, 0x00, 0, 0);
+    }
+}
+
+// F.1.2.1: the low bits of v, of v - 1 for negative values
+fn extra_bits(v: i32, size: u8) -> u32 {
+    let v = if v < 0 { v - 1 } else { v };
+    v as u32 & ((1_u32 << size) - 1)
+}
+
+fn marker(out: &mut Vec<u8>, code: u8, body: &[u8]) {
+    out.extend_from_slice(&[0xff, code]);
+    out.extend_from_slice(&(body.len() as u16 + 2).to_be_bytes());
+    out.extend_from_slice(body);
+}
+
+fn write_headers(out: &mut Vec<u8>, width: usize, height: usize, coded: &[Coded],
+    tables: &[[u16;64]], dc: &[HuffmanSpec;2], ac: &[HuffmanSpec;2]) {
+    out.extend_from_slice(&[0xff, 0xd8]);
+    // JFIF 1.01, no units, 1:1 aspect ratio, no thumbnail
+    marker(out, 0xe0, &[b'J', b'F', b'I', b'F', 0, 1, 1, 0, 0, 1, 0, 1, 0, 0]);
+
+    // a segment per table like libjpeg, 8 bit entries in zigzag order
+    for (i, table) in tables.iter().enumerate() {
+        let mut dqt = vec![i as u8];
+        dqt.extend(to_zigzag(table).iter().map(|q| *q as u8));
+        marker(out, 0xdb, &dqt);
+    }
+
+    let mut sof = vec![8];
+    sof.extend_from_slice(&(height as u16).to_be_bytes());
+    sof.extend_from_slice(&(width as u16).to_be_bytes());
+    sof.push(coded.len() as u8);
+    for (i, c) in coded.iter().enumerate() {
+        sof.extend_from_slice(&[i as u8 + 1, (c.h << 4 | c.v) as u8, c.table as u8]);
+    }
+    marker(out, 0xc0, &sof);
+
+    // DC then AC table of each class
+    for i in 0..tables.len() {
+        for (class, spec) in [(0x00, &dc[i]), (0x10, &ac[i])] {
+            let mut dht = vec![class | i as u8];
+            dht.extend_from_slice(&spec.bits);
+            dht.extend_from_slice(&spec.values);
+            marker(out, 0xc4, &dht);
+        }
+    }
+
+    // all components, the whole spectral range
+    let mut sos = vec![coded.len() as u8];
+    for (i, c) in coded.iter().enumerate() {
+        sos.extend_from_slice(&[i as u8 + 1, (c.table << 4 | c.table) as u8]);
+    }
+    sos.extend_from_slice(&[0, 63, 0]);
+    marker(out, 0xda, &sos);
+}
+
+struct BitWriter {
+    out: Vec<u8>,
+    // the count pending bits, in the low bits
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    // length up to 16 bits, whole bytes go out with a 0 stuffed after 0xff
+    fn put(&mut self, code: u32, length: u32) {
+        self.bits = self.bits << length | code;
+        self.count += length;
+        while self.count >= 8 {
+            self.count -= 8;
+            let byte = (self.bits >> self.count) as u8;
+            self.out.push(byte);
+            if byte == 0xff {
+                self.out.push(0);
+            }
+        }
+        self.bits &= (1 << self.count) - 1;
+    }
+
+    // the last byte padded with 1 bits (F.1.2.3)
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.put((1 << (8 - self.count)) - 1, 8 - self.count);
+        }
+        self.out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::decoder;
+    use crate::fdct;
+    use crate::transform::{IslowIdct, LlmFdct, StandardFdct};
+
+    // decoded libjpeg output: (name, width, height, components, pixels)
+    const IMAGES: [(&str, usize, usize, usize, &[u8]);3] = [
+        ("gray", 33, 27, 1, include_bytes!("../testdata/gray.raw")),
+        ("color_420", 45, 37, 3, include_bytes!("../testdata/color_420.raw")),
+        ("color_422_scans", 40, 21, 3, include_bytes!("../testdata/color_422_scans.raw")),
+    ];
+
+    fn psnr(a: &[u8], b: &[u8]) -> f64 {
+        let sse: f64 = a.iter().zip(b).map(|(a, b)| (*a as f64 - *b as f64).powi(2)).sum();
+        10.0 * (255.0 * 255.0 * a.len() as f64 / sse.max(1e-9)).log10()
+    }
+
+    fn round_trip(jpeg: &[u8], width: usize, height: usize, pixels: &[u8]) -> f64 {
+        let image = decoder::decode::<IslowIdct>(jpeg).unwrap();
+        assert_eq!((image.width, image.height), (width, height));
+        psnr(&image.pixels(), pixels)
+    }
+
+    // quality against PSNR for every subsampling, the FDCT of fdct.rs and
+    // llm_fdct give the same files up to a few coefficients
+    #[test]
+    fn round_trip_psnr() {
+        for (name, width, height, components, pixels) in IMAGES {
+            for subsampling in [Subsampling::S444, Subsampling::S422, Subsampling::S420] {
+                let mut last = 0.0;
+                for (quality, min) in [(25, 18.0), (50, 21.0), (75, 24.0), (95, 37.0)] {
+                    let options = EncodeOptions { quality, subsampling, optimize_huffman: false };
+                    let llm = round_trip(&encode::<LlmFdct>(pixels, width, height, components, &options),
+                        width, height, pixels);
+                    let standard = round_trip(&encode::<StandardFdct>(pixels, width, height, components, &options),
+                        width, height, pixels);
+                    let message = format!("{} {:?} quality {}: {:.2} dB, standard {:.2} dB",
+                        name, subsampling, quality, llm, standard);
+                    assert!((llm - standard).abs() < 0.1, "{}", message);
+                    assert!(llm > last, "{}", message);
+                    // color_422_scans has full vertical chroma detail, 4:2:0
+                    // stays near 20 dB at any quality
+                    if name != "color_422_scans" || subsampling != Subsampling::S420 {
+                        assert!(llm > min, "{}", message);
+                    }
+                    last = llm;
+                }
+            }
+        }
+    }
+
+    // made with libjpeg-turbo from the .raw files above: JDCT_ISLOW, cjpeg
+    // defaults otherwise, (file, image, quality, subsampling, optimize)
+    const LIBJPEG: [(&[u8], usize, u32, Subsampling, bool);4] = [
+        (include_bytes!("../testdata/enc_gray_q50.jpg"), 0, 50, Subsampling::S444, false),
+        (include_bytes!("../testdata/enc_420_q80_opt.jpg"), 1, 80, Subsampling::S420, true),
+        (include_bytes!("../testdata/enc_422_q90.jpg"), 2, 90, Subsampling::S422, false),
+        (include_bytes!("../testdata/enc_444_q30_opt.jpg"), 2, 30, Subsampling::S444, true),
+    ];
+
+    // color conversion, downsampling, padding, quantization and Huffman
+    // tables all as libjpeg: the same bytes
+    #[test]
+    fn islow_matches_libjpeg() {
+        for (want, image, quality, subsampling, optimize_huffman) in LIBJPEG {
+            let (name, width, height, components, pixels) = IMAGES[image];
+            let options = EncodeOptions { quality, subsampling, optimize_huffman };
+            let jpeg = encode_quantize(pixels, width, height, components, &options,
+                fdct::islow_fdct_divisors, fdct::islow_fdct_quantize);
+            assert!(jpeg == want, "{} quality {}", name, quality);
+        }
+    }
+
+    // gray at quality 100 is all but lossless, subsampled chroma loses
+    // more than full chroma
+    #[test]
+    fn high_quality() {
+        let (_, width, height, _, pixels) = IMAGES[0];
+        let options = EncodeOptions { quality: 100, ..Default::default() };
+        let jpeg = encode::<LlmFdct>(pixels, width, height, 1, &options);
+        assert!(round_trip(&jpeg, width, height, pixels) > 50.0);
+
+        let (_, width, height, _, pixels) = IMAGES[1];
+        let db = |subsampling| {
+            let options = EncodeOptions { quality: 100, subsampling, optimize_huffman: false };
+            round_trip(&encode::<LlmFdct>(pixels, width, height, 3, &options), width, height, pixels)
+        };
+        assert!(db(Subsampling::S444) > db(Subsampling::S422));
+        assert!(db(Subsampling::S422) > db(Subsampling::S420));
+    }
+
+    // optimal tables code the same coefficients in fewer bytes
+    #[test]
+    fn optimized_huffman() {
+        for (name, width, height, components, pixels) in IMAGES {
+            for quality in [30, 90] {
+                let options = EncodeOptions { quality, ..Default::default() };
+                let standard = encode::<LlmFdct>(pixels, width, height, components, &options);
+                let options = EncodeOptions { optimize_huffman: true, ..options };
+                let optimized = encode::<LlmFdct>(pixels, width, height, components, &options);
+                assert!(optimized.len() < standard.len(), "{} quality {}: {} vs {} bytes",
+                    name, quality, optimized.len(), standard.len());
+                let decode = |jpeg: &[u8]| decoder::decode::<IslowIdct>(jpeg).unwrap().pixels();
+                assert_eq!(decode(&optimized), decode(&standard), "{} quality {}", name, quality);
+            }
+        }
+    }
+
+    // sizes that are not whole blocks or MCUs, a single pixel and
+    // saturated colors
+    #[test]
+    fn edges() {
+        for (width, height) in [(1, 1), (7, 9), (17, 3), (16, 16)] {
+            let pixels: Vec<u8> = (0..width * height * 3)
+                .map(|i| [0, 255, 255, 0, 0, 255, 128][i % 7]).collect();
+            for subsampling in [Subsampling::S444, Subsampling::S422, Subsampling::S420] {
+                let options = EncodeOptions { quality: 90, subsampling, optimize_huffman: true };
+                let jpeg = encode::<LlmFdct>(&pixels, width, height, 3, &options);
+                let image = decoder::decode::<IslowIdct>(&jpeg).unwrap();
+                assert_eq!((image.width, image.height), (width, height));
+                assert_eq!(image.pixels().len(), pixels.len());
+            }
+        }
+    }
+
+    #[test]
+    fn color_conversion() {
+        assert_eq!(rgb_to_ycc(0, 0, 0), [0, 128, 128]);
+        assert_eq!(rgb_to_ycc(255, 255, 255), [255, 128, 128]);
+        assert_eq!(rgb_to_ycc(255, 0, 0), [76, 85, 255]);
+        assert_eq!(rgb_to_ycc(0, 0, 255), [29, 255, 107]);
+    }
+
+    #[test]
+    #[should_panic(expected = "pixels for")]
+    fn wrong_pixel_count() {
+        encode::<LlmFdct>(&[0; 10], 4, 4, 1, &EncodeOptions::default());
+    }
+}
@@ -1,5 +1,11 @@
 //use core::f32::consts::PI;
 
+use crate::idct::{descale, AAN_SCALES, CONST_BITS, PASS1_BITS};
+use crate::idct::{FIX_0_298631336, FIX_0_390180644, FIX_0_541196100, FIX_0_765366865,
+    FIX_0_899976223, FIX_1_175875602, FIX_1_501321110, FIX_1_847759065,
+    FIX_1_961570560, FIX_2_053119869, FIX_2_562915447, FIX_3_072711026};
+
+#[allow(clippy::excessive_precision, clippy::approx_constant)]
 pub fn fdct(f :&[u8]) -> Vec<f32> {
 
     let cos = [
@@ -29,6 +35,7 @@ pub fn fdct(f :&[u8]) -> Vec<f32> {
     vals
 }
 
+#[allow(clippy::excessive_precision, clippy::identity_op, clippy::erasing_op)]
 pub fn llm_fdct(f:&[u8]) -> Vec<f32> {
     let m0 = 0.7071067811865475;
     let m1 = 1.3870398453221475;
@@ -57,7 +64,7 @@ pub fn llm_fdct(f:&[u8]) -> Vec<f32> {
         let a2 = f2 + f5;
         let a5 = f2 - f5;
         let a3 = f3 + f4;
-        let a4 = f4 - f4;
+        let a4 = f3 - f4;
 
 
         let c0 = a0 + a3;
@@ -106,7 +113,7 @@ pub fn llm_fdct(f:&[u8]) -> Vec<f32> {
         let a2 = f2 + f5;
         let a5 = f2 - f5;
         let a3 = f3 + f4;
-        let a4 = f4 - f4;
+        let a4 = f3 - f4;
 
         let c0 = a0 + a3;
         let c3 = a0 - a3;
@@ -143,6 +150,344 @@ pub fn llm_fdct(f:&[u8]) -> Vec<f32> {
     zz.to_vec()
 }
 
+// AAN forward DCT, ports of IJG jfdctflt.c (jpeg_fdct_float) and
+// jfdctfst.c (jpeg_fdct_ifast). 5 multiplies per 1D pass because every
+// output is left scaled by 8 * s[v] * s[u] (s below); the scale goes into
+// the quantization divisors, as libjpeg's jcdctmgr.c does, so FDCT plus
+// quantization is one multiply per coefficient.
+// Same operation order as libjpeg-turbo: the float and fixed point outputs
+// are bit exact with jpeg_fdct_float and jpeg_fdct_ifast.
+
+// aanscalefactor in jcdctmgr.c: s[0] = 1, s[k] = cos(kπ/16) * √2
+const AAN_SCALE_FACTOR: [f64;8] = [
+    1.0, 1.387039845, 1.306562965, 1.175875602,
+    1.0, 0.785694958, 0.541196100, 0.275899379];
+
+// one jfdctflt 1D pass over d[0], d[step], .. d[7 * step]
+#[inline(always)]
+#[allow(clippy::excessive_precision, clippy::approx_constant)]
+fn aan_fdct_1d(d: &mut [f32;64], i: usize, step: usize) {
+    let tmp0 = d[i] + d[i + 7 * step];
+    let tmp7 = d[i] - d[i + 7 * step];
+    let tmp1 = d[i + step] + d[i + 6 * step];
+    let tmp6 = d[i + step] - d[i + 6 * step];
+    let tmp2 = d[i + 2 * step] + d[i + 5 * step];
+    let tmp5 = d[i + 2 * step] - d[i + 5 * step];
+    let tmp3 = d[i + 3 * step] + d[i + 4 * step];
+    let tmp4 = d[i + 3 * step] - d[i + 4 * step];
+
+    // even part
+    let tmp10 = tmp0 + tmp3;
+    let tmp13 = tmp0 - tmp3;
+    let tmp11 = tmp1 + tmp2;
+    let tmp12 = tmp1 - tmp2;
+
+    d[i] = tmp10 + tmp11;
+    d[i + 4 * step] = tmp10 - tmp11;
+
+    let z1 = (tmp12 + tmp13) * 0.707106781; // c4
+    d[i + 2 * step] = tmp13 + z1;
+    d[i + 6 * step] = tmp13 - z1;
+
+    // odd part
+    let tmp10 = tmp4 + tmp5;
+    let tmp11 = tmp5 + tmp6;
+    let tmp12 = tmp6 + tmp7;
+
+    // the rotator is modified from fig 4-8 to avoid extra negations
+    let z5 = (tmp10 - tmp12) * 0.382683433; // c6
+    let z2 = 0.541196100 * tmp10 + z5;      // c2 - c6
+    let z4 = 1.306562965 * tmp12 + z5;      // c2 + c6
+    let z3 = tmp11 * 0.707106781;           // c4
+
+    let z11 = tmp7 + z3;
+    let z13 = tmp7 - z3;
+
+    d[i + 5 * step] = z13 + z2;
+    d[i + 3 * step] = z13 - z2;
+    d[i + step] = z11 + z4;
+    d[i + 7 * step] = z11 - z4;
+}
+
+// f is 64 samples, out[v*8 + u] is F(v, u) * 8 * s[v] * s[u]
+pub fn aan_fdct_scaled(f: &[u8;64], out: &mut [f32;64]) {
+    for (d, s) in out.iter_mut().zip(f.iter()) {
+        *d = *s as f32 - 128.0;
+    }
+    for j in 0..8 {
+        aan_fdct_1d(out, j * 8, 1);
+    }
+    for i in 0..8 {
+        aan_fdct_1d(out, i, 8);
+    }
+}
+
+// normalized like fdct, for comparisons; an encoder uses aan_fdct_quantize
+pub fn aan_fdct(f: &[u8]) -> Vec<f32> {
+    let mut out = [0_f32;64];
+    aan_fdct_scaled(f[..64].try_into().unwrap(), &mut out);
+    for (i, val) in out.iter_mut().enumerate() {
+        *val = (*val as f64 / (8.0 * AAN_SCALE_FACTOR[i / 8] * AAN_SCALE_FACTOR[i % 8])) as f32;
+    }
+    out.to_vec()
+}
+
+// one time per quantization table: 1 / (q * 8 * s[v] * s[u])
+pub fn aan_fdct_divisors(q: &[u16;64]) -> [f32;64] {
+    let mut table = [0_f32;64];
+    for (i, val) in table.iter_mut().enumerate() {
+        *val = (1.0 / (q[i] as f64 * AAN_SCALE_FACTOR[i / 8] * AAN_SCALE_FACTOR[i % 8] * 8.0)) as f32;
+    }
+    table
+}
+
+// quantized coefficients in natural order, rounded like quantize_float
+// in libjpeg-turbo jcdctmgr.c. Its SSE2 version rounds ties to even, so
+// cjpeg -dct float on x86 can write one less on an exact .5
+pub fn aan_fdct_quantize(f: &[u8;64], divisors: &[f32;64], out: &mut [i32;64]) {
+    let mut ws = [0_f32;64];
+    aan_fdct_scaled(f, &mut ws);
+    for i in 0..64 {
+        out[i] = (ws[i] * divisors[i] + 16384.5) as i32 - 16384;
+    }
+}
+
+// jfdctfst: 8 bit constants, products truncated (no USE_ACCURATE_ROUNDING)
+const IFAST_CONST_BITS: i32 = 8;
+const IFAST_FIX_0_382683433: i32 = 98;  // FIX(0.382683433)
+const IFAST_FIX_0_541196100: i32 = 139; // FIX(0.541196100)
+const IFAST_FIX_0_707106781: i32 = 181; // FIX(0.707106781)
+const IFAST_FIX_1_306562965: i32 = 334; // FIX(1.306562965)
+
+#[inline(always)]
+fn ifast_mul(x: i32, c: i32) -> i32 {
+    (x * c) >> IFAST_CONST_BITS
+}
+
+// one jfdctfst 1D pass, the float pass with truncating fixed point products.
+// 8 bit samples never leave 16 bits, so DCTELEM int and short agree
+#[inline(always)]
+fn ifast_fdct_1d(d: &mut [i32;64], i: usize, step: usize) {
+    let tmp0 = d[i] + d[i + 7 * step];
+    let tmp7 = d[i] - d[i + 7 * step];
+    let tmp1 = d[i + step] + d[i + 6 * step];
+    let tmp6 = d[i + step] - d[i + 6 * step];
+    let tmp2 = d[i + 2 * step] + d[i + 5 * step];
+    let tmp5 = d[i + 2 * step] - d[i + 5 * step];
+    let tmp3 = d[i + 3 * step] + d[i + 4 * step];
+    let tmp4 = d[i + 3 * step] - d[i + 4 * step];
+
+    // even part
+    let tmp10 = tmp0 + tmp3;
+    let tmp13 = tmp0 - tmp3;
+    let tmp11 = tmp1 + tmp2;
+    let tmp12 = tmp1 - tmp2;
+
+    d[i] = tmp10 + tmp11;
+    d[i + 4 * step] = tmp10 - tmp11;
+
+    let z1 = ifast_mul(tmp12 + tmp13, IFAST_FIX_0_707106781);
+    d[i + 2 * step] = tmp13 + z1;
+    d[i + 6 * step] = tmp13 - z1;
+
+    // odd part
+    let tmp10 = tmp4 + tmp5;
+    let tmp11 = tmp5 + tmp6;
+    let tmp12 = tmp6 + tmp7;
+
+    let z5 = ifast_mul(tmp10 - tmp12, IFAST_FIX_0_382683433);
+    let z2 = ifast_mul(tmp10, IFAST_FIX_0_541196100) + z5;
+    let z4 = ifast_mul(tmp12, IFAST_FIX_1_306562965) + z5;
+    let z3 = ifast_mul(tmp11, IFAST_FIX_0_707106781);
+
+    let z11 = tmp7 + z3;
+    let z13 = tmp7 - z3;
+
+    d[i + 5 * step] = z13 + z2;
+    d[i + 3 * step] = z13 - z2;
+    d[i + step] = z11 + z4;
+    d[i + 7 * step] = z11 - z4;
+}
+
+// f is 64 samples, out[v*8 + u] is about F(v, u) * 8 * s[v] * s[u]
+pub fn ifast_fdct_scaled(f: &[u8;64], out: &mut [i32;64]) {
+    for (d, s) in out.iter_mut().zip(f.iter()) {
+        *d = *s as i32 - 128;
+    }
+    for j in 0..8 {
+        ifast_fdct_1d(out, j * 8, 1);
+    }
+    for i in 0..8 {
+        ifast_fdct_1d(out, i, 8);
+    }
+}
+
+// normalized like fdct, for comparisons; an encoder uses ifast_fdct_quantize
+pub fn ifast_fdct(f: &[u8]) -> Vec<f32> {
+    let mut out = [0_i32;64];
+    ifast_fdct_scaled(f[..64].try_into().unwrap(), &mut out);
+    out.iter().zip(AAN_SCALES.iter())
+        .map(|(v, s)| (*v as f64 * 2048.0 / *s as f64) as f32)
+        .collect()
+}
+
+// division by a constant as one multiply and a shift, compute_reciprocal in
+// libjpeg-turbo jcdctmgr.c (16 bit DCTELEM). divide(x) is
+// (|x| + d / 2) / d with the sign of x, the IJG rounding, for |x| < 2^15
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Reciprocal {
+    recip: u32,
+    corr: u32,
+    shift: u32,
+}
+
+impl Reciprocal {
+    pub fn new(d: u16) -> Self {
+        let d = d as u32;
+        assert!(d > 0, "divisor 0");
+        if d == 1 {
+            return Reciprocal { recip: 1, corr: 0, shift: 0 };
+        }
+        let b = 31 - d.leading_zeros(); // flss(d) - 1
+        let mut r = 16 + b;
+        let mut fq = (1_u32 << r) / d;
+        let fr = (1_u32 << r) % d;
+        let mut c = d / 2; // rounding
+        if fr == 0 {
+            // d is a power of 2
+            fq >>= 1;
+            r -= 1;
+        } else if fr <= d / 2 {
+            c += 1;
+        } else {
+            fq += 1;
+        }
+        Reciprocal { recip: fq, corr: c, shift: r }
+    }
+
+    #[inline(always)]
+    pub fn divide(&self, x: i32) -> i32 {
+        let t = x.unsigned_abs();
+        let q = (((t + self.corr) as u64 * self.recip as u64) >> self.shift) as i32;
+        if x < 0 {-q} else {q}
+    }
+}
+
+// one time per quantization table: reciprocals of q * 8 * s[v] * s[u],
+// DESCALE(q * aanscales, 11) as jcdctmgr.c
+pub fn ifast_fdct_divisors(q: &[u16;64]) -> [Reciprocal;64] {
+    core::array::from_fn(|i| {
+        let d = (q[i] as i64 * AAN_SCALES[i] as i64 + (1 << 10)) >> 11;
+        Reciprocal::new(d.clamp(1, u16::MAX as i64) as u16)
+    })
+}
+
+// quantized coefficients in natural order, same values as libjpeg's
+// quantize() for JDCT_IFAST
+pub fn ifast_fdct_quantize(f: &[u8;64], divisors: &[Reciprocal;64], out: &mut [i32;64]) {
+    let mut ws = [0_i32;64];
+    ifast_fdct_scaled(f, &mut ws);
+    for i in 0..64 {
+        out[i] = divisors[i].divide(ws[i]);
+    }
+}
+
+// LL&M integer FDCT, port of IJG jfdctint.c (jpeg_fdct_islow, JDCT_ISLOW)
+// 13 bit constants, PASS1_BITS = 2 extra bits kept between the passes,
+// rounding descale. Output scaled up by 8 like libjpeg, bit exact with
+// libjpeg-turbo, so quantized with islow_fdct_divisors the coefficients are
+// the ones cjpeg -dct int writes.
+
+// one jfdctint 1D pass over d[0], d[step], .. d[7 * step]
+#[inline(always)]
+fn islow_fdct_1d(d: &mut [i32;64], i: usize, step: usize, pass1: bool) {
+    let tmp0 = d[i] + d[i + 7 * step];
+    let tmp7 = d[i] - d[i + 7 * step];
+    let tmp1 = d[i + step] + d[i + 6 * step];
+    let tmp6 = d[i + step] - d[i + 6 * step];
+    let tmp2 = d[i + 2 * step] + d[i + 5 * step];
+    let tmp5 = d[i + 2 * step] - d[i + 5 * step];
+    let tmp3 = d[i + 3 * step] + d[i + 4 * step];
+    let tmp4 = d[i + 3 * step] - d[i + 4 * step];
+
+    // rows keep PASS1_BITS more, columns remove them
+    let shift = if pass1 {CONST_BITS - PASS1_BITS} else {CONST_BITS + PASS1_BITS};
+
+    // even part
+    let tmp10 = tmp0 + tmp3;
+    let tmp13 = tmp0 - tmp3;
+    let tmp11 = tmp1 + tmp2;
+    let tmp12 = tmp1 - tmp2;
+
+    if pass1 {
+        d[i] = (tmp10 + tmp11) << PASS1_BITS;
+        d[i + 4 * step] = (tmp10 - tmp11) << PASS1_BITS;
+    } else {
+        d[i] = descale(tmp10 + tmp11, PASS1_BITS);
+        d[i + 4 * step] = descale(tmp10 - tmp11, PASS1_BITS);
+    }
+
+    let z1 = (tmp12 + tmp13) * FIX_0_541196100;
+    d[i + 2 * step] = descale(z1 + tmp13 * FIX_0_765366865, shift);
+    d[i + 6 * step] = descale(z1 + tmp12 * -FIX_1_847759065, shift);
+
+    // odd part, tmp4..tmp7 are i0..i3 of the paper
+    let z1 = tmp4 + tmp7;
+    let z2 = tmp5 + tmp6;
+    let z3 = tmp4 + tmp6;
+    let z4 = tmp5 + tmp7;
+    let z5 = (z3 + z4) * FIX_1_175875602; // sqrt(2) * c3
+
+    let tmp4 = tmp4 * FIX_0_298631336; // sqrt(2) * (-c1+c3+c5-c7)
+    let tmp5 = tmp5 * FIX_2_053119869; // sqrt(2) * ( c1+c3-c5+c7)
+    let tmp6 = tmp6 * FIX_3_072711026; // sqrt(2) * ( c1+c3+c5-c7)
+    let tmp7 = tmp7 * FIX_1_501321110; // sqrt(2) * ( c1+c3-c5-c7)
+    let z1 = z1 * -FIX_0_899976223;    // sqrt(2) * ( c7-c3)
+    let z2 = z2 * -FIX_2_562915447;    // sqrt(2) * (-c1-c3)
+    let z3 = z3 * -FIX_1_961570560 + z5; // sqrt(2) * (-c3-c5)
+    let z4 = z4 * -FIX_0_390180644 + z5; // sqrt(2) * ( c5-c3)
+
+    d[i + 7 * step] = descale(tmp4 + z1 + z3, shift);
+    d[i + 5 * step] = descale(tmp5 + z2 + z4, shift);
+    d[i + 3 * step] = descale(tmp6 + z2 + z3, shift);
+    d[i + step] = descale(tmp7 + z1 + z4, shift);
+}
+
+// f is 64 samples, out[v*8 + u] is F(v, u) * 8 rounded
+pub fn islow_fdct_scaled(f: &[u8;64], out: &mut [i32;64]) {
+    for (d, s) in out.iter_mut().zip(f.iter()) {
+        *d = *s as i32 - 128;
+    }
+    for j in 0..8 {
+        islow_fdct_1d(out, j * 8, 1, true);
+    }
+    for i in 0..8 {
+        islow_fdct_1d(out, i, 8, false);
+    }
+}
+
+// normalized like fdct, for comparisons; an encoder uses islow_fdct_quantize
+pub fn islow_fdct(f: &[u8]) -> Vec<f32> {
+    let mut out = [0_i32;64];
+    islow_fdct_scaled(f[..64].try_into().unwrap(), &mut out);
+    out.iter().map(|v| *v as f32 * 0.125).collect()
+}
+
+// one time per quantization table: reciprocals of q * 8, as jcdctmgr.c
+pub fn islow_fdct_divisors(q: &[u16;64]) -> [Reciprocal;64] {
+    core::array::from_fn(|i| Reciprocal::new((q[i] as u32 * 8).min(u16::MAX as u32) as u16))
+}
+
+// quantized coefficients in natural order, same values as libjpeg's
+// quantize() for JDCT_ISLOW
+pub fn islow_fdct_quantize(f: &[u8;64], divisors: &[Reciprocal;64], out: &mut [i32;64]) {
+    let mut ws = [0_i32;64];
+    islow_fdct_scaled(f, &mut ws);
+    for i in 0..64 {
+        out[i] = divisors[i].divide(ws[i]);
+    }
+}
+
 pub fn print_vec_f32(f:&[f32]) -> String {
     let mut str = "".to_string();
     for i in 0..8{
@@ -151,4 +496,266 @@ pub fn print_vec_f32(f:&[f32]) -> String {
         );
     }
     str
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::Ieee1180Rand;
+    use crate::quant::LUMINANCE as LUMA;
+    use crate::idct;
+
+    // random blocks, then flat, extreme and high frequency ones
+    fn samples() -> Vec<Vec<u8>> {
+        let mut rand = Ieee1180Rand::new();
+        let mut blocks: Vec<Vec<u8>> = (0..2000)
+            .map(|_| (0..64).map(|_| rand.next(0, 255) as u8).collect())
+            .collect();
+        for level in [0, 1, 127, 128, 254, 255] {
+            blocks.push(vec![level; 64]);
+        }
+        let pattern = |f: fn(usize, usize) -> bool| -> Vec<u8> {
+            (0..64).map(|i| if f(i % 8, i / 8) {255} else {0}).collect()
+        };
+        blocks.push(pattern(|x, y| (x + y) % 2 == 0)); // checkerboard, F[7][7]
+        blocks.push(pattern(|x, _| x % 2 == 0));       // vertical lines
+        blocks.push(pattern(|_, y| y < 4));            // horizontal edge
+        blocks.push(pattern(|x, y| x == 0 && y == 0)); // one pixel
+        blocks.push(pattern(|x, y| x == 7 && y == 7));
+        blocks.push((0..64).map(|i| (i * 4) as u8).collect()); // ramp
+        blocks
+    }
+
+    #[test]
+    fn llm_fdct_matches_fdct() {
+        for f in samples() {
+            let want = fdct(&f);
+            let got = llm_fdct(&f);
+            for (i, (a, b)) in want.iter().zip(&got).enumerate() {
+                assert!((a - b).abs() < 1e-3, "coefficient {}: {} vs {}, samples {:?}", i, a, b, f);
+            }
+        }
+    }
+
+    // integer coefficients back through the f64 IDCT, 1 at most from rounding
+    fn round_trip(forward: fn(&[u8]) -> Vec<f32>) {
+        let mut total = 0;
+        let blocks = samples();
+        for f in &blocks {
+            let coefs: Vec<i32> = forward(f).iter().map(|v| v.round() as i32).collect();
+            let out = idct::idct_f64(&coefs);
+            for (a, b) in f.iter().zip(&out) {
+                let err = (*a as i32 - *b as i32).abs();
+                assert!(err <= 1, "error {}, samples {:?}", err, f);
+                total += err;
+            }
+        }
+        let mean = total as f64 / (blocks.len() * 64) as f64;
+        assert!(mean < 0.1, "mean error {}", mean);
+    }
+
+    #[test]
+    fn aan_fdct_matches_fdct() {
+        for f in samples() {
+            let want = fdct(&f);
+            let got = aan_fdct(&f);
+            for (i, (a, b)) in want.iter().zip(&got).enumerate() {
+                assert!((a - b).abs() < 1e-3, "coefficient {}: {} vs {}, samples {:?}", i, a, b, f);
+            }
+        }
+    }
+
+    // truncated 8 bit products, about 5 at most
+    #[test]
+    fn ifast_fdct_is_near_fdct() {
+        for f in samples() {
+            let want = fdct(&f);
+            let got = ifast_fdct(&f);
+            for (i, (a, b)) in want.iter().zip(&got).enumerate() {
+                assert!((a - b).abs() < 6.0, "coefficient {}: {} vs {}, samples {:?}", i, a, b, f);
+            }
+        }
+    }
+
+    // jpeg_fdct_islow rounds twice and has 13 bit constants, within 2 units
+    // of the exact 8x output
+    #[test]
+    fn islow_fdct_is_near_fdct() {
+        for f in samples() {
+            let want = fdct(&f);
+            let got = islow_fdct(&f);
+            for (i, (a, b)) in want.iter().zip(&got).enumerate() {
+                assert!((a - b).abs() < 0.25, "coefficient {}: {} vs {}, samples {:?}", i, a, b, f);
+            }
+        }
+    }
+
+    // fixed block for the libjpeg-turbo outputs below
+    fn fixed_block() -> [u8;64] {
+        core::array::from_fn(|i| {
+            let (x, y) = (i % 8, i / 8);
+            ((x * 37 + y * 11 + (x * y) % 7 * 13) % 256) as u8
+        })
+    }
+
+    // libjpeg-turbo jpeg_fdct_islow output for a fixed block
+    #[test]
+    fn islow_fdct_matches_libjpeg() {
+        let f = fixed_block();
+        let want = [
+            -1178,   631, -3332,  -627,   222,  -390,  -596,   265,
+             -420, -1240,  -410,  1196,  -781,   439,   246,  -116,
+             -379,  -323,   386,     9,   180,   339,  -564,   618,
+             -477,    15,   765,  -345,  -401,   513,  -535,   -68,
+              222,  -573,   -97,   627,   -74,   -16,   -40,   255,
+              795,  -350,  -294,   514,  -703,   -89,   447,  -175,
+             -157,  -334,   459,   239,  -709,   493,   490,  -749,
+              376,  -117,  -364,   168,   214,   -19,  -234,   -77];
+        let mut got = [0;64];
+        islow_fdct_scaled(&f, &mut got);
+        assert_eq!(got, want);
+    }
+
+    // random block whose scaled DC over q = 1 is -13.5 exactly, quantize
+    // rounds the tie up
+    fn tie_block() -> [u8;64] {
+        samples()[15][..].try_into().unwrap()
+    }
+
+    // libjpeg-turbo jpeg_fdct_float output for the fixed block, then the
+    // coefficients cjpeg -dct float writes (C quantize_float, JSIMD_FORCENONE)
+    #[test]
+    fn aan_fdct_matches_libjpeg() {
+        let want = [
+            -1178.0, 874.5851, -4353.254, -736.79596, 222.0, -306.8104, -322.74573, 73.02126,
+            -583.1587, -2385.2449, -742.8056, 1951.2148, -1082.9933, 478.5381, 184.83298, -44.508125,
+            -495.06097, -584.7726, 658.38684, 13.779312, 235.71277, 348.25934, -399.03864, 222.73392,
+            -560.74915, 25.084778, 1175.2527, -477.78867, -471.42075, 474.5082, -340.18765, -21.804382,
+            222.0, -794.48584, -126.32593, 737.0981, -74.0, -13.020691, -21.674088, 70.40844,
+            624.2782, -381.40704, -301.21405, 474.5082, -552.5792, -55.07309, 190.22635, -38.028038,
+            -84.93904, -250.29247, 325.0387, 152.32544, -383.71277, 209.7132, 143.61314, -111.74622,
+            103.62967, -44.508057, -131.2331, 54.40509, 58.993286, -4.0033417, -34.87168, -5.8936157];
+        let mut got = [0_f32;64];
+        aan_fdct_scaled(&fixed_block(), &mut got);
+        assert_eq!(got, want);
+
+        let want = [
+            -9,   7, -42,  -5,   1,  -1,  -1,   1,
+            -4, -13,  -4,   8,  -4,   1,   1,   0,
+            -3,  -3,   3,   0,   1,   1,  -1,   1,
+            -4,   0,   4,  -1,  -1,   1,  -1,   0,
+             2,  -3,   0,   1,   0,   0,   0,   0,
+             4,  -1,  -1,   1,  -1,   0,   0,   0,
+             0,  -1,   1,   0,  -1,   1,   1,  -1,
+             1,   0,   0,   0,   0,   0,   0,   0];
+        let mut got = [0;64];
+        aan_fdct_quantize(&fixed_block(), &aan_fdct_divisors(&LUMA), &mut got);
+        assert_eq!(got, want);
+
+        let want = [
+             -13,   11,   47,  -41,  -15,  -49,   17,  -12,
+              98,   90,  137,  -29, -161,  -30, -119,   84,
+              72,  111,  -30,   34,  -31,    6,  -29,  246,
+             -80,    6,   87,  -87,    0,    7,  -75,    4,
+             -20,   20,   78,   15,   80,   63,   93,  -16,
+            -111,  -11,  -67,  -64,  -48,  -82,   98,  -38,
+              69,  -10,   41,  -89,   72,   88,  -15, -144,
+              23,   73,  -23,   66,  -88,   29,   -8,   25];
+        aan_fdct_quantize(&tie_block(), &aan_fdct_divisors(&[1;64]), &mut got);
+        assert_eq!(got, want);
+    }
+
+    // libjpeg-turbo jpeg_fdct_ifast output for the fixed block, then the
+    // coefficients cjpeg -dct fast writes, through Reciprocal
+    #[test]
+    fn ifast_fdct_matches_libjpeg() {
+        let want = [
+            -1178,   862, -4357,  -726,   222,  -310,  -319,    78,
+             -583, -2384,  -743,  1950, -1082,   475,   183,   -46,
+             -496,  -584,   660,    13,   235,   348,  -402,   221,
+             -560,    28,  1177,  -479,  -471,   477,  -338,   -21,
+              222,  -796,  -125,   740,   -74,   -12,   -23,    68,
+              624,  -382,  -303,   477,  -553,   -57,   190,   -39,
+              -84,  -250,   326,   153,  -383,   210,   144,  -111,
+              103,   -46,  -131,    56,    58,    -3,   -35,    -6];
+        let mut got = [0;64];
+        ifast_fdct_scaled(&fixed_block(), &mut got);
+        assert_eq!(got, want);
+
+        let want = [
+            -9,   7, -41,  -5,   1,  -1,  -1,   1,
+            -4, -13,  -4,   8,  -4,   1,   1,   0,
+            -3,  -3,   3,   0,   1,   1,  -1,   1,
+            -4,   0,   4,  -1,  -1,   1,  -1,   0,
+             2,  -3,   0,   1,   0,   0,   0,   0,
+             4,  -1,  -1,   1,  -1,   0,   0,   0,
+             0,  -1,   1,   0,  -1,   1,   1,  -1,
+             1,   0,   0,   0,   0,   0,   0,   0];
+        ifast_fdct_quantize(&fixed_block(), &ifast_fdct_divisors(&LUMA), &mut got);
+        assert_eq!(got, want);
+
+        let want = [
+             -14,   10,   48,  -42,  -15,  -52,   20,  -11,
+              99,   92,  141,  -29, -162,  -29, -120,   85,
+              75,  115,  -29,   35,  -32,    7,  -27,  237,
+             -84,    6,   90,  -87,    0,    7,  -76,    4,
+             -20,   20,   82,   15,   80,   66,  101,  -18,
+            -116,  -10,  -69,  -68,  -50,  -81,  112,  -34,
+              75,  -10,   39,  -90,   78,  100,  -17, -172,
+              27,   75,  -22,   57,  -98,   26,   -9,   16];
+        ifast_fdct_quantize(&tie_block(), &ifast_fdct_divisors(&[1;64]), &mut got);
+        assert_eq!(got, want);
+    }
+
+    // FDCT and quantization in one multiply against round(fdct / q)
+    #[test]
+    fn folded_quantization() {
+        let aan = aan_fdct_divisors(&LUMA);
+        let ifast = ifast_fdct_divisors(&LUMA);
+        let islow = islow_fdct_divisors(&LUMA);
+        let (mut aan_off, mut ifast_off, mut islow_off, mut total) = (0, 0, 0, 0);
+        let mut a = [0;64];
+        let mut b = [0;64];
+        let mut c = [0;64];
+        for f in samples() {
+            let block: &[u8;64] = f[..].try_into().unwrap();
+            aan_fdct_quantize(block, &aan, &mut a);
+            ifast_fdct_quantize(block, &ifast, &mut b);
+            islow_fdct_quantize(block, &islow, &mut c);
+            for (i, want) in fdct(&f).iter().enumerate() {
+                let want = (want / LUMA[i] as f32).round() as i32;
+                assert!((a[i] - want).abs() <= 1 && (b[i] - want).abs() <= 1, "samples {:?}", f);
+                assert!((c[i] - want).abs() <= 1, "samples {:?}", f);
+                aan_off += (a[i] != want) as usize;
+                ifast_off += (b[i] != want) as usize;
+                islow_off += (c[i] != want) as usize;
+                total += 1;
+            }
+        }
+        // ties and rounding near .5
+        assert!(aan_off * 200 < total, "aan {} of {}", aan_off, total);
+        assert!(ifast_off * 50 < total, "ifast {} of {}", ifast_off, total);
+        assert!(islow_off * 200 < total, "islow {} of {}", islow_off, total);
+    }
+
+    #[test]
+    fn reciprocal_is_rounded_division() {
+        for d in [1_u16, 2, 3, 5, 7, 8, 10, 64, 255, 1000, 4080, 32767] {
+            let r = Reciprocal::new(d);
+            for x in -32767_i32..32768 {
+                let want = (x.abs() + d as i32 / 2) / d as i32 * x.signum();
+                assert_eq!(r.divide(x), want, "{} / {}", x, d);
+            }
+        }
+    }
+
+    #[test]
+    fn fdct_round_trip() {
+        round_trip(fdct);
+    }
+
+    #[test]
+    fn llm_fdct_round_trip() {
+        round_trip(llm_fdct);
+    }
+}
@@ -0,0 +1,380 @@
+// Huffman tables of the baseline entropy coder (T.81 Annex C, Annex K.3)
+//
+// A HuffmanSpec is a table as sent in a DHT segment: bits[i] is the number
+// of codes of length i + 1, values the symbols in order of increasing code
+// length. DC symbols are the magnitude category SSSS of the difference, AC
+// symbols RRRRSSSS (run of zeros, category), 0x00 EOB and 0xF0 ZRL.
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuffmanSpec {
+    pub bits: [u8;16],
+    pub values: Vec<u8>,
+}
+
+// T.81 Table K.3
+const DC_LUMINANCE_BITS: [u8;16] = [0, 1, 5, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0, 0, 0];
+const DC_LUMINANCE_VALUES: [u8;12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+// T.81 Table K.4
+const DC_CHROMINANCE_BITS: [u8;16] = [0, 3, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0, 0];
+const DC_CHROMINANCE_VALUES: [u8;12] = [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11];
+
+// T.81 Table K.5
+const AC_LUMINANCE_BITS: [u8;16] = [0, 2, 1, 3, 3, 2, 4, 3, 5, 5, 4, 4, 0, 0, 1, 0x7d];
+const AC_LUMINANCE_VALUES: [u8;162] = [
+    0x01, 0x02, 0x03, 0x00, 0x04, 0x11, 0x05, 0x12,
+    0x21, 0x31, 0x41, 0x06, 0x13, 0x51, 0x61, 0x07,
+    0x22, 0x71, 0x14, 0x32, 0x81, 0x91, 0xa1, 0x08,
+    0x23, 0x42, 0xb1, 0xc1, 0x15, 0x52, 0xd1, 0xf0,
+    0x24, 0x33, 0x62, 0x72, 0x82, 0x09, 0x0a, 0x16,
+    0x17, 0x18, 0x19, 0x1a, 0x25, 0x26, 0x27, 0x28,
+    0x29, 0x2a, 0x34, 0x35, 0x36, 0x37, 0x38, 0x39,
+    0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48, 0x49,
+    0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58, 0x59,
+    0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68, 0x69,
+    0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78, 0x79,
+    0x7a, 0x83, 0x84, 0x85, 0x86, 0x87, 0x88, 0x89,
+    0x8a, 0x92, 0x93, 0x94, 0x95, 0x96, 0x97, 0x98,
+    0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5, 0xa6, 0xa7,
+    0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4, 0xb5, 0xb6,
+    0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3, 0xc4, 0xc5,
+    0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2, 0xd3, 0xd4,
+    0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda, 0xe1, 0xe2,
+    0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9, 0xea,
+    0xf1, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa];
+
+// T.81 Table K.6
+const AC_CHROMINANCE_BITS: [u8;16] = [0, 2, 1, 2, 4, 4, 3, 4, 7, 5, 4, 4, 0, 1, 2, 0x77];
+const AC_CHROMINANCE_VALUES: [u8;162] = [
+    0x00, 0x01, 0x02, 0x03, 0x11, 0x04, 0x05, 0x21,
+    0x31, 0x06, 0x12, 0x41, 0x51, 0x07, 0x61, 0x71,
+    0x13, 0x22, 0x32, 0x81, 0x08, 0x14, 0x42, 0x91,
+    0xa1, 0xb1, 0xc1, 0x09, 0x23, 0x33, 0x52, 0xf0,
+    0x15, 0x62, 0x72, 0xd1, 0x0a, 0x16, 0x24, 0x34,
+    0xe1, 0x25, 0xf1, 0x17, 0x18, 0x19, 0x1a, 0x26,
+    0x27, 0x28, 0x29, 0x2a, 0x35, 0x36, 0x37, 0x38,
+    0x39, 0x3a, 0x43, 0x44, 0x45, 0x46, 0x47, 0x48,
+    0x49, 0x4a, 0x53, 0x54, 0x55, 0x56, 0x57, 0x58,
+    0x59, 0x5a, 0x63, 0x64, 0x65, 0x66, 0x67, 0x68,
+    0x69, 0x6a, 0x73, 0x74, 0x75, 0x76, 0x77, 0x78,
+    0x79, 0x7a, 0x82, 0x83, 0x84, 0x85, 0x86, 0x87,
+    0x88, 0x89, 0x8a, 0x92, 0x93, 0x94, 0x95, 0x96,
+    0x97, 0x98, 0x99, 0x9a, 0xa2, 0xa3, 0xa4, 0xa5,
+    0xa6, 0xa7, 0xa8, 0xa9, 0xaa, 0xb2, 0xb3, 0xb4,
+    0xb5, 0xb6, 0xb7, 0xb8, 0xb9, 0xba, 0xc2, 0xc3,
+    0xc4, 0xc5, 0xc6, 0xc7, 0xc8, 0xc9, 0xca, 0xd2,
+    0xd3, 0xd4, 0xd5, 0xd6, 0xd7, 0xd8, 0xd9, 0xda,
+    0xe2, 0xe3, 0xe4, 0xe5, 0xe6, 0xe7, 0xe8, 0xe9,
+    0xea, 0xf2, 0xf3, 0xf4, 0xf5, 0xf6, 0xf7, 0xf8,
+    0xf9, 0xfa];
+
+impl HuffmanSpec {
+    pub fn new(bits: [u8;16], values: Vec<u8>) -> Self {
+        let count: usize = bits.iter().map(|b| *b as usize).sum();
+        assert_eq!(count, values.len(), "bits count {} codes, {} values given", count, values.len());
+        HuffmanSpec { bits, values }
+    }
+
+    pub fn dc_luminance() -> Self {
+        Self::new(DC_LUMINANCE_BITS, DC_LUMINANCE_VALUES.to_vec())
+    }
+
+    pub fn dc_chrominance() -> Self {
+        Self::new(DC_CHROMINANCE_BITS, DC_CHROMINANCE_VALUES.to_vec())
+    }
+
+    pub fn ac_luminance() -> Self {
+        Self::new(AC_LUMINANCE_BITS, AC_LUMINANCE_VALUES.to_vec())
+    }
+
+    pub fn ac_chrominance() -> Self {
+        Self::new(AC_CHROMINANCE_BITS, AC_CHROMINANCE_VALUES.to_vec())
+    }
+
+    // code length of every symbol, 0 for the symbols without a code
+    // (C.2 HUFFSIZE indexed by symbol)
+    pub fn code_lengths(&self) -> [u8;256] {
+        let mut lengths = [0_u8;256];
+        let mut values = self.values.iter();
+        for (i, count) in self.bits.iter().enumerate() {
+            for v in values.by_ref().take(*count as usize) {
+                lengths[*v as usize] = i as u8 + 1;
+            }
+        }
+        lengths
+    }
+
+    // code and length of every symbol (C.2 HUFFSIZE, C.3 HUFFCODE in
+    // symbol order), None when more codes are given than fit in 16 bits
+    pub fn codes(&self) -> Option<[(u16, u8);256]> {
+        let mut codes = [(0_u16, 0_u8);256];
+        let mut code = 0_u32;
+        let mut values = self.values.iter();
+        for (i, count) in self.bits.iter().enumerate() {
+            let length = i as u32 + 1;
+            for v in values.by_ref().take(*count as usize) {
+                if code >= 1 << length {
+                    return None;
+                }
+                codes[*v as usize] = (code as u16, length as u8);
+                code += 1;
+            }
+            code <<= 1;
+        }
+        Some(codes)
+    }
+
+    // optimal table for the symbol counts freq, codes at most 16 bits long
+    // and none of all ones (K.2, jchuff.c jpeg_gen_optimal_table)
+    pub fn optimal(freq: &[u32;256]) -> Self {
+        const MAX_LENGTH: usize = 32;
+        // symbol 256 reserves the all ones code point, it gets the longest
+        // code and is removed at the end
+        let mut freq: Vec<u64> = freq.iter().map(|f| *f as u64).chain([1]).collect();
+        let mut code_size = [0_usize;257];
+        // next symbol in the tree branch of a symbol
+        let mut others = [usize::MAX;257];
+
+        // the two least frequent symbols, the higher one first on ties
+        let smallest = |freq: &[u64], skip: usize| {
+            let mut found = None;
+            let mut v = u64::MAX;
+            for (i, f) in freq.iter().enumerate() {
+                if *f > 0 && *f <= v && i != skip {
+                    v = *f;
+                    found = Some(i);
+                }
+            }
+            found
+        };
+        while let Some(mut c1) = smallest(&freq, usize::MAX) {
+            let Some(mut c2) = smallest(&freq, c1) else { break };
+            freq[c1] += freq[c2];
+            freq[c2] = 0;
+            code_size[c1] += 1;
+            while others[c1] != usize::MAX {
+                c1 = others[c1];
+                code_size[c1] += 1;
+            }
+            others[c1] = c2;
+            code_size[c2] += 1;
+            while others[c2] != usize::MAX {
+                c2 = others[c2];
+                code_size[c2] += 1;
+            }
+        }
+
+        let mut bits = [0_usize;MAX_LENGTH + 1];
+        for size in code_size.iter().filter(|s| **s > 0) {
+            bits[*size] += 1;
+        }
+        // K.3 Adjust_BITS: move pairs of the longest codes up a level
+        for i in (17..=MAX_LENGTH).rev() {
+            while bits[i] > 0 {
+                let mut j = i - 2;
+                while bits[j] == 0 {
+                    j -= 1;
+                }
+                bits[i] -= 2;
+                bits[i - 1] += 1;
+                bits[j + 1] += 2;
+                bits[j] -= 1;
+            }
+        }
+        // drop the reserved code, one of the longest
+        let longest = (1..=16).rev().find(|i| bits[*i] > 0).unwrap();
+        bits[longest] -= 1;
+
+        let mut values = Vec::new();
+        for size in 1..=MAX_LENGTH {
+            values.extend((0..256).filter(|v| code_size[*v] == size).map(|v| v as u8));
+        }
+        Self::new(core::array::from_fn(|i| bits[i + 1] as u8), values)
+    }
+}
+
+// bits of the fast decode lookup, codes up to this length take one load
+const LOOKUP_BITS: u32 = 9;
+
+// decoding table of F.2.2.3 (MAXCODE, VALPTR - MINCODE) with a lookup of
+// the first LOOKUP_BITS bits for the short codes
+#[derive(Debug, Clone)]
+pub struct DecodeTable {
+    // length << 8 | symbol, 0 for prefixes of longer codes
+    lookup: [u16;1 << LOOKUP_BITS],
+    maxcode: [i32;17],
+    valoffset: [i32;17],
+    values: Vec<u8>,
+}
+
+impl DecodeTable {
+    // None for a table with too many codes
+    pub fn new(spec: &HuffmanSpec) -> Option<Self> {
+        let codes = spec.codes()?;
+        let mut table = DecodeTable {
+            lookup: [0;1 << LOOKUP_BITS],
+            maxcode: [-1;17],
+            valoffset: [0;17],
+            values: spec.values.clone(),
+        };
+        let mut code = 0_i32;
+        let mut k = 0_i32;
+        for length in 1..=16 {
+            let count = spec.bits[length - 1] as i32;
+            if count > 0 {
+                table.valoffset[length] = k - code;
+                code += count;
+                k += count;
+                table.maxcode[length] = code - 1;
+            }
+            code <<= 1;
+        }
+        for v in &spec.values {
+            let (code, length) = codes[*v as usize];
+            let length = length as u32;
+            if length <= LOOKUP_BITS {
+                // every lookup index that starts with the code
+                let shift = LOOKUP_BITS - length;
+                let first = (code as usize) << shift;
+                table.lookup[first..first + (1 << shift)].fill((length as u16) << 8 | *v as u16);
+            }
+        }
+        Some(table)
+    }
+
+    // bits holds the next 16 bits of the stream, first bit in bit 15;
+    // the symbol and its code length, None for a bit string with no code
+    #[inline]
+    pub fn decode(&self, bits: u32) -> Option<(u8, u32)> {
+        let fast = self.lookup[(bits >> (16 - LOOKUP_BITS)) as usize];
+        if fast != 0 {
+            return Some((fast as u8, (fast >> 8) as u32));
+        }
+        for length in LOOKUP_BITS as usize + 1..=16 {
+            let code = (bits >> (16 - length)) as i32;
+            if code <= self.maxcode[length] {
+                return Some((self.values[(code + self.valoffset[length]) as usize], length as u32));
+            }
+        }
+        None
+    }
+}
+
+// magnitude category SSSS of a DC difference or AC coefficient (F.1.2.1),
+// also the number of extra bits after the Huffman code
+#[inline]
+pub fn category(v: i32) -> u8 {
+    (32 - v.unsigned_abs().leading_zeros()) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn annex_k_code_lengths() {
+        let dc = HuffmanSpec::dc_luminance().code_lengths();
+        assert_eq!(dc[..12], [2, 3, 3, 3, 3, 3, 4, 5, 6, 7, 8, 9]);
+        let dc = HuffmanSpec::dc_chrominance().code_lengths();
+        assert_eq!(dc[..12], [2, 2, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]);
+
+        // EOB, 0/1, 0/2, ZRL and the longest codes of Table K.5
+        let ac = HuffmanSpec::ac_luminance().code_lengths();
+        assert_eq!([ac[0x00], ac[0x01], ac[0x02], ac[0xf0], ac[0xfa]], [4, 2, 2, 11, 16]);
+        let ac = HuffmanSpec::ac_chrominance().code_lengths();
+        assert_eq!([ac[0x00], ac[0x01], ac[0x02], ac[0xf0], ac[0xfa]], [2, 2, 3, 10, 16]);
+
+        // every run / category pair of a baseline AC table has a code
+        for spec in [HuffmanSpec::ac_luminance(), HuffmanSpec::ac_chrominance()] {
+            let lengths = spec.code_lengths();
+            for run in 0..16 {
+                for size in 1..11 {
+                    assert!(lengths[run << 4 | size] > 0, "{} / {}", run, size);
+                }
+            }
+        }
+    }
+
+    // Table K.5 codes from the text of Annex K
+    #[test]
+    fn annex_k_codes() {
+        let codes = HuffmanSpec::ac_luminance().codes().unwrap();
+        assert_eq!(codes[0x01], (0b00, 2));
+        assert_eq!(codes[0x00], (0b1010, 4));
+        assert_eq!(codes[0x11], (0b1100, 4));
+        assert_eq!(codes[0xf0], (0b11111111001, 11));
+        assert_eq!(codes[0xfa], (0b1111111111111110, 16));
+        let codes = HuffmanSpec::dc_luminance().codes().unwrap();
+        assert_eq!(codes[0], (0b00, 2));
+        assert_eq!(codes[11], (0b111111110, 9));
+
+        let mut bits = [0;16];
+        bits[0] = 3;
+        assert!(HuffmanSpec::new(bits, vec![0, 1, 2]).codes().is_none());
+    }
+
+    // every code decodes to its symbol, whatever bits follow it
+    #[test]
+    fn decode_table() {
+        for spec in [HuffmanSpec::dc_luminance(), HuffmanSpec::dc_chrominance(),
+            HuffmanSpec::ac_luminance(), HuffmanSpec::ac_chrominance()] {
+            let table = DecodeTable::new(&spec).unwrap();
+            let codes = spec.codes().unwrap();
+            for v in &spec.values {
+                let (code, length) = codes[*v as usize];
+                for tail in [0, 0x5555, 0xffff] {
+                    let bits = (code as u32) << (16 - length) | (tail >> length);
+                    assert_eq!(table.decode(bits), Some((*v, length as u32)));
+                }
+            }
+            // the all ones code is never assigned
+            assert_eq!(table.decode(0xffff), None);
+        }
+    }
+
+    #[test]
+    fn categories() {
+        assert_eq!(category(0), 0);
+        assert_eq!(category(1), 1);
+        assert_eq!(category(-1), 1);
+        assert_eq!(category(-3), 2);
+        assert_eq!(category(4), 3);
+        assert_eq!(category(1023), 10);
+        assert_eq!(category(-2047), 11);
+    }
+
+    // shorter codes for the frequent symbols, a valid table for counts
+    // whose Huffman tree is deeper than 16 levels
+    #[test]
+    fn optimal_tables() {
+        let mut freq = [0_u32;256];
+        freq[..4].copy_from_slice(&[100, 50, 25, 25]);
+        let spec = HuffmanSpec::optimal(&freq);
+        assert_eq!(spec.values, [0, 1, 2, 3]);
+        // 1, 2, 3 bits and the reserved code point taking 3 + 1
+        let lengths = spec.code_lengths();
+        assert_eq!(lengths[..4], [1, 2, 3, 4]);
+        assert!(DecodeTable::new(&spec).is_some());
+
+        // Fibonacci counts, the unlimited tree is 30 levels deep
+        let mut freq = [0_u32;256];
+        let (mut a, mut b) = (1, 1);
+        for f in freq.iter_mut().take(30) {
+            *f = a;
+            (a, b) = (b, a + b);
+        }
+        let spec = HuffmanSpec::optimal(&freq);
+        let lengths = spec.code_lengths();
+        assert!((0..30).all(|v| (1..=16).contains(&lengths[v])));
+        assert!(lengths[29] <= lengths[0]);
+        let table = DecodeTable::new(&spec).unwrap();
+        assert_eq!(table.decode(0xffff), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "bits count")]
+    fn wrong_value_count() {
+        HuffmanSpec::new(DC_LUMINANCE_BITS, vec![0, 1, 2]);
+    }
+}
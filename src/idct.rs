@@ -1,32 +1,50 @@
 
 use core::f32::consts::PI;
 use core::f64::consts::PI as PI_f64;
+use std::sync::OnceLock;
+use std::sync::atomic::{AtomicU8, Ordering};
+use core::ops::{Add, Mul, Sub};
+
+use crate::zigzag::ZIGZAG_ORDER;
 
 pub fn idct_f64(f :&[i32]) -> Vec<u8> {
-    let vals :Vec<u8> = (0..64).map(|i| {
+    let mut vals = [0_u8;64];
+    idct_f64_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+#[allow(clippy::assign_op_pattern)]
+pub fn idct_f64_into(f :&[i32;64], vals :&mut [u8;64]) {
+    for (i, val_out) in vals.iter_mut().enumerate() {
         let (x,y) = ((i%8) as f64,(i/8) as f64);
         // IDCT from CCITT Rec. T.81 (1992 E) p.27 A3.3
         let mut val: f64=0.0;
         for u in 0..8 {
             let cu = if u == 0 {1.0 / 2.0_f64.sqrt()} else {1.0};
             for v in 0..8 {
-                let cv = if v == 0 {1.0_f64/ 2.0_64_f64.sqrt()} else {1.0};
+                let cv = if v == 0 {1.0_f64/ 2.0_f64.sqrt()} else {1.0};
                 val += cu * cv * (f[v*8 + u] as f64)
-                    * ((2.0 * x + 1.0_64) * u as f64 * PI_f64 / 16.0_f64).cos()
-                    * ((2.0 * y + 1.0_64) * v as f64 * PI_f64 / 16.0_f64).cos();
+                    * ((2.0 * x + 1.0) * u as f64 * PI_f64 / 16.0_f64).cos()
+                    * ((2.0 * y + 1.0) * v as f64 * PI_f64 / 16.0_f64).cos();
             }
         }
         val = val / 4.0;
 
         // level shift from CCITT Rec. T.81 (1992 E) p.26 A3.1
         let v = val.round() as i32 + 128;
-        v.clamp(0,255) as u8
-    }).collect();
-    vals
+        *val_out = v.clamp(0,255) as u8;
+    }
 }
 
 pub fn idct(f :&[i32]) -> Vec<u8> {
-    let vals :Vec<u8> = (0..64).map(|i| {
+    let mut vals = [0_u8;64];
+    idct_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+#[allow(clippy::assign_op_pattern)]
+pub fn idct_into(f :&[i32;64], vals :&mut [u8;64]) {
+    for (i, val_out) in vals.iter_mut().enumerate() {
         let (x,y) = ((i%8) as f32,(i/8) as f32);
         // IDCT from CCITT Rec. T.81 (1992 E) p.27 A3.3
         let mut val: f32=0.0;
@@ -43,13 +61,189 @@ pub fn idct(f :&[i32]) -> Vec<u8> {
 
         // level shift from CCITT Rec. T.81 (1992 E) p.26 A3.1
         let v = val.round() as i32 + 128;
-        v.clamp(0,255) as u8
-    }).collect();
-    vals
+        *val_out = v.clamp(0,255) as u8;
+    }
 }
 
 // LL&M 2D IDCT is 1D IDCT * 1D IDCT 
 pub fn llm_idct(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    llm_idct_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+pub fn llm_idct_into(f: &[i32;64], vals: &mut [u8;64]) {
+    llm_idct_plane(f, vals, 0, 8);
+}
+
+// like libjpeg jpeg_idct_*: row y of the block is written to
+// plane[offset + y * stride..][..8]
+pub fn llm_idct_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ff = load_f32(f);
+    llm_idct_inplace(&mut ff);
+    store_truncate(&ff, plane, offset, stride);
+}
+
+// coefficients in, spatial values (before level shift) out
+pub fn llm_idct_inplace(ff: &mut [f32;64]) {
+    llm_idct_kernel::<true>(ff, 0.125); // √2/4 * √2/4
+}
+
+// LL&M with quantization folded in, see llm_prepare_quant
+// the final 1/8 is already in the table, so the dequantize costs nothing
+pub fn llm_idct_dequant(f: &[i32;64], table: &[f32;64], vals: &mut [u8;64]) {
+    let mut ff = [0_f32;64];
+    for i in 0..64 {
+        ff[i] = f[i] as f32 * table[i];
+    }
+    llm_idct_kernel::<true>(&mut ff, 1.0);
+    store_truncate(&ff, vals, 0, 8);
+}
+
+// one time per quantization table: q * √2/4 * √2/4
+pub fn llm_prepare_quant(q: &[u16;64]) -> [f32;64] {
+    let mut table = [0_f32;64];
+    for i in 0..64 {
+        table[i] = q[i] as f32 * 0.125;
+    }
+    table
+}
+
+// arithmetic of the 1D passes below: f32 for the scalar kernels, vectors of
+// 8 blocks (batch.rs) or of the rows / columns of a block (x86.rs,
+// portable.rs) for the others. No FMA, so every lane type gives the bits
+// of the scalar kernel
+pub(crate) trait Lanes: Copy + Add<Output = Self> + Sub<Output = Self> + Mul<Output = Self> {
+    fn splat(x: f32) -> Self;
+}
+
+impl Lanes for f32 {
+    #[inline(always)]
+    fn splat(x: f32) -> Self {
+        x
+    }
+}
+
+// one LL&M 1D pass, f[k] is coefficient k, out[k] is sample k
+#[inline(always)]
+#[allow(clippy::excessive_precision, clippy::approx_constant)]
+pub(crate) fn llm_1d<V: Lanes>(f: [V;8]) -> [V;8] {
+    let m1 = V::splat(0.5411961);   // α √2cos(3π/8)
+    let m2 = V::splat(1.306562965); // β √2cos(3π/8)
+    let m3 = V::splat(1.414213562); // γ v2
+    let m4 = V::splat(0.831469612); // η cos(3π/16)
+    let m5 = V::splat(0.555570233); // θ sin(3π/16)
+    let m6 = V::splat(0.98078528);  // δ cos(π/16)
+    let m7 = V::splat(0.195090322); // ε sin(π/16)
+    let [f0, f1, f2, f3, f4, f5, f6, f7] = f;
+
+    // even part
+    let y0 = f0 + f4;
+    let y1 = f0 - f4;
+    let y2 = m1 * f2 - m2 * f6;
+    let y3 = m1 * f6 + m2 * f2;
+
+    let x0 = y0 + y3;
+    let x1 = y1 + y2;
+    let x2 = y1 - y2;
+    let x3 = y0 - y3;
+
+    // odd part
+    let z4 = f1 - f7;
+    let z5 = f3 * m3;
+    let z6 = f5 * m3;
+    let z7 = f1 + f7;
+
+    let y4 = z4 + z6;
+    let y5 = z7 - z5;
+    let y6 = z4 - z6;
+    let y7 = z7 + z5;
+
+    let x4 = y4 * m4 - y7 * m5;
+    let x5 = y5 * m6 - y6 * m7;
+    let x6 = y6 * m6 + y5 * m7;
+    let x7 = y7 * m4 + y4 * m5;
+
+    [x0 + x7, x1 + x6, x2 + x5, x3 + x4, x3 - x4, x2 - x5, x1 - x6, x0 - x7]
+}
+
+// m0 is the last pass scale, 1.0 when it is folded into the input
+#[inline(always)]
+pub(crate) fn llm_idct_kernel<const PRUNE: bool>(ff: &mut [f32;64], m0: f32) {
+    // rows
+    for row in ff.chunks_exact_mut(8) {
+        if PRUNE && row[1..].iter().all(|v| *v == 0.0) {
+            // AC terms all zero, every output is the DC term
+            let dc = row[0];
+            row.fill(dc);
+            continue;
+        }
+        let out = llm_1d(core::array::from_fn(|u| row[u]));
+        row.copy_from_slice(&out);
+    }
+    // columns, then the last pass scale (√2/4 * √2/4)
+    for i in 0..8 {
+        let out = llm_1d(core::array::from_fn(|v| ff[v*8 + i]));
+        for (v, o) in out.into_iter().enumerate() {
+            ff[v*8 + i] = o * m0;
+        }
+    }
+}
+
+// LL&M with an end of block hint, eob is the zigzag index of the last
+// non zero coefficient (0 for a DC only block), as the Huffman decoder
+// knows it. Up to index 2 all coefficients are in the top left 2x2, up
+// to 9 in the top left 4x4, so the passes only touch those rows and
+// the zero terms are left out of the butterflies. Dropping a zero term
+// does not change an f32 result, the output is bit identical to
+// llm_idct_into. A wrong hint gives a wrong block.
+pub fn llm_idct_eob(f: &[i32;64], eob: usize, vals: &mut [u8;64]) {
+    llm_idct_eob_plane(f, eob, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn llm_idct_eob_plane(f: &[i32;64], eob: usize, plane: &mut [u8], offset: usize, stride: usize) {
+    if eob == 0 {
+        // every sample is dc, stored like store_truncate
+        let dc = ((f[0] as f32 * 0.125 + 128.0) as i32).clamp(0,255) as u8;
+        for y in 0..8 {
+            plane[offset + y * stride..][..8].fill(dc);
+        }
+        return;
+    }
+    let mut ff = [0_f32;64];
+    match eob {
+        1..=2 => llm_idct_sparse::<2>(f, &mut ff, 0.125),
+        3..=9 => llm_idct_sparse::<4>(f, &mut ff, 0.125),
+        _ => {
+            ff = load_f32(f);
+            llm_idct_kernel::<true>(&mut ff, 0.125);
+        }
+    }
+    store_truncate(&ff, plane, offset, stride);
+}
+
+// llm_idct_kernel for a block with only the top left K x K (2 or 4)
+// coefficients non zero
+#[inline(always)]
+fn llm_idct_sparse<const K: usize>(f: &[i32;64], ff: &mut [f32;64], m0: f32) {
+    let mut ws = [[0_f32;8];4];
+    for (j, row) in ws.iter_mut().take(K).enumerate() {
+        let i = j * 8;
+        *row = llm_idct_1d_sparse::<K>(&[f[i] as f32, f[i + 1] as f32, f[i + 2] as f32, f[i + 3] as f32]);
+    }
+    for i in 0..8 {
+        let col = llm_idct_1d_sparse::<K>(&[ws[0][i], ws[1][i], ws[2][i], ws[3][i]]);
+        for (j, val) in col.iter().enumerate() {
+            ff[j * 8 + i] = val * m0;
+        }
+    }
+}
+
+// one LL&M 1D pass with f4..f7 zero, and f2, f3 too when K is 2
+#[inline(always)]
+#[allow(clippy::excessive_precision, clippy::approx_constant)]
+fn llm_idct_1d_sparse<const K: usize>(f: &[f32;4]) -> [f32;8] {
     let m1 = 0.5411961;   // α √2cos(3π/8)
     let m2 = 1.306562965; // β √2cos(3π/8)
     let m3 = 1.414213562; // γ v2
@@ -57,290 +251,188 @@ pub fn llm_idct(f: &[i32]) -> Vec<u8> {
     let m5 = 0.555570233; // θ sin(3π/16)
     let m6 = 0.98078528;  // δ cos(π/16)
     let m7 = 0.195090322; // ε sin(π/16)
-    let m0 = 0.125; // √2/4 * √2/4
 
-    let mut ff = [0_f32;64];
-    for j in 0..8 {
-        let i = j * 8;
-        let f0 = f[0 + i] as f32;  // X0
-        let f1 = f[1 + i] as f32;  // X1
-        let f2 = f[2 + i] as f32;  // X2
-        let f3 = f[3 + i] as f32;  // X3
-        let f4 = f[4 + i] as f32;  // X4
-        let f6 = f[6 + i] as f32;  // X5
-        let f5 = f[5 + i] as f32;  // X6
-        let f7 = f[7 + i] as f32;  // X7
+    let f0 = f[0];
+    let f1 = f[1];
+    if K == 2 {
+        // even part is f0 alone, y4 = y5 = y6 = y7 = f1
+        let x4 = f1 * m4 - f1 * m5;
+        let x5 = f1 * m6 - f1 * m7;
+        let x6 = f1 * m6 + f1 * m7;
+        let x7 = f1 * m4 + f1 * m5;
+        return [f0 + x7, f0 + x6, f0 + x5, f0 + x4, f0 - x4, f0 - x5, f0 - x6, f0 - x7];
+    }
+    let f2 = f[2];
+    let f3 = f[3];
 
-        // implement batterfly mutilply
+    // even part, y0 = y1 = f0
+    let y2 = m1 * f2;
+    let y3 = m2 * f2;
 
-        // even part
-        // part 2
-
-        let y0 = f0 + f4;
-        let y1 = f0 - f4;
-        let y2 = m1 * f2 - m2 * f6;
-        let y3 = m1 * f6 + m2 * f2;
-
-        // part3
-
-        let x0 = y0 + y3;
-        let x1 = y1 + y2;
-        let x2 = y1 - y2;
-        let x3 = y0 - y3;
-
-        // odds part
-
-        // part 1
-        let z4 = f1 - f7;
-        let z5 = f3 * m3;
-        let z6 = f5 * m3;
-        let z7 = f1 + f7;
-
-        // part 2
-        let y4 = z4 + z6;
-        let y5 = z7 - z5;
-        let y6 = z4 - z6;
-        let y7 = z7 + z5;
-
-        // part 3
-        let x4 = y4 * m4 - y7 * m5;
-        let x5 = y5 * m6 - y6 * m7;
-        let x6 = y6 * m6 + y5 * m7;
-        let x7 = y7 * m4 + y4 * m5;
-
-        // last part  multiply √2 / 4 after parts
-
-        ff[0 + i] = x0 + x7;   // x0
-        ff[7 + i] = x0 - x7;   // x1
-        ff[1 + i] = x1 + x6;   // x2
-        ff[6 + i] = x1 - x6;   // x3
-        ff[2 + i] = x2 + x5;   // x4
-        ff[5 + i] = x2 - x5;   // x5
-        ff[3 + i] = x3 + x4;   // x6
-        ff[4 + i] = x3 - x4;   // x7
-    }
-    for i in 0..8 {
-        let f0 = ff[0 * 8 + i];
-        let f1 = ff[1 * 8 + i];
-        let f2 = ff[2 * 8 + i];
-        let f3 = ff[3 * 8 + i];
-        let f4 = ff[4 * 8 + i];
-        let f5 = ff[5 * 8 + i];
-        let f6 = ff[6 * 8 + i];
-        let f7 = ff[7 * 8 + i];
-
-        // odds part
-        // part 1
+    let x0 = f0 + y3;
+    let x1 = f0 + y2;
+    let x2 = f0 - y2;
+    let x3 = f0 - y3;
 
-        // even part
-        // part 2
-
-        let y0 = f0 + f4;
-        let y1 = f0 - f4;
-        let y2 = m1 * f2 - m2 * f6;
-        let y3 = m1 * f6 + m2 * f2;
-
-        // part3
-
-        let x0 = y0 + y3;
-        let x1 = y1 + y2;
-        let x2 = y1 - y2;
-        let x3 = y0 - y3;
-
-        // odds part
-        // part 1
-        let z4 = f1 - f7;
-        let z5 = f3 * m3;
-        let z6 = f5 * m3;
-        let z7 = f1 + f7;
-
-        // part 2
-        let y4 = z4 + z6;
-        let y5 = z7 - z5;
-        let y6 = z4 - z6;
-        let y7 = z7 + z5;
-
-
-        let x4 = y4 * m4 - y7 * m5;
-        let x5 = y5 * m6 - y6 * m7;
-        let x6 = y6 * m6 + y5 * m7;
-        let x7 = y7 * m4 + y4 * m5;
-
-        ff[0 * 8 + i] = (x0 + x7) * m0;
-        ff[7 * 8 + i] = (x0 - x7) * m0;
-        ff[1 * 8 + i] = (x1 + x6) * m0; 
-        ff[6 * 8 + i] = (x1 - x6) * m0;
-        ff[2 * 8 + i] = (x2 + x5) * m0;
-        ff[5 * 8 + i] = (x2 - x5) * m0;
-        ff[3 * 8 + i] = (x3 + x4) * m0; 
-        ff[4 * 8 + i] = (x3 - x4) * m0;  
-    }
-    
-    let val = ff.iter().map(|i| ((*i + 128.0) as i32).clamp(0,255) as u8).collect();
-    val
+    // odd part, z4 = z7 = y4 = y6 = f1
+    let z5 = f3 * m3;
+    let y5 = f1 - z5;
+    let y7 = f1 + z5;
+
+    let x4 = f1 * m4 - y7 * m5;
+    let x5 = y5 * m6 - f1 * m7;
+    let x6 = f1 * m6 + y5 * m7;
+    let x7 = y7 * m4 + f1 * m5;
+
+    [x0 + x7, x1 + x6, x2 + x5, x3 + x4, x3 - x4, x2 - x5, x1 - x6, x0 - x7]
 }
+
 // AAN
 pub fn fast_idct(f: &[i32]) -> Vec<u8> {
-    let mut _f  = [0_f32;64];
     let mut vals = [0_u8;64];
-    let m0 = 1.847759;
-    let m1 = 1.4142135;
-    let m3 = 1.4142135;
-    let m5 = 0.76536685;
-    let m2 = m0 - m5;
-    let m4 = m0 + m5;
+    fast_idct_into(block(f), &mut vals);
+    vals.to_vec()
+}
 
-    let s0 = 0.35355338;
-    let s1 = 0.49039263;
-    let s2 = 0.46193975;
-    let s3 = 0.4157348;
-    let s4 = 0.35355338;
-    let s5 = 0.2777851;
-    let s6 = 0.19134171;
-    let s7 = 0.09754512;
-    
-    for i in 0..8 {
-        let g0 = f[0*8 + i] as f32 * s0;
-        let g1 = f[4*8 + i] as f32 * s4;
-        let g2 = f[2*8 + i] as f32 * s2;
-        let g3 = f[6*8 + i] as f32 * s6;
-        let g4 = f[5*8 + i] as f32 * s5;
-        let g5 = f[1*8 + i] as f32 * s1;
-        let g6 = f[7*8 + i] as f32 * s7;
-        let g7 = f[3*8 + i] as f32 * s3;
-    
-        let f0 = g0;
-        let f1 = g1;
-        let f2 = g2;
-        let f3 = g3;
-        let f4 = g4 - g7;
-        let f5 = g5 + g6;
-        let f6 = g5 - g6;
-        let f7 = g4 + g7;
-    
-        let e0 = f0;
-        let e1 = f1;
-        let e2 = f2 - f3;
-        let e3 = f2 + f3;
-        let e4 = f4;
-        let e5 = f5 - f7;
-        let e6 = f6;
-        let e7 = f5 + f7;
-        let e8 = f4 + f6;
-    
-        let d0 = e0;
-        let d1 = e1;
-        let d2 = e2 * m1;
-        let d3 = e3;
-        let d4 = e4 * m2;
-        let d5 = e5 * m3;
-        let d6 = e6 * m4;
-        let d7 = e7;
-        let d8 = e8 * m5;
-    
-        let c0 = d0 + d1;
-        let c1 = d0 - d1;
-        let c2 = d2 - d3;
-        let c3 = d3;
-        let c4 = d4 + d8;
-        let c5 = d5 + d7;
-        let c6 = d6 - d8;
-        let c7 = d7;
-        let c8 = c5 - c6;
-    
-        let b0 = c0 + c3;
-        let b1 = c1 + c2;
-        let b2 = c1 - c2;
-        let b3 = c0 - c3;
-        let b4 = c4 - c8;
-        let b5 = c8;
-        let b6 = c6 - c7;
-        let b7 = c7;
-        
-        _f[0 * 8 + i] = b0 + b7;
-        _f[1 * 8 + i] = b1 + b6;
-        _f[2 * 8 + i] = b2 + b5;
-        _f[3 * 8 + i] = b3 + b4;
-        _f[4 * 8 + i] = b3 - b4;
-        _f[5 * 8 + i] = b2 - b5;
-        _f[6 * 8 + i] = b1 - b6;
-        _f[7 * 8 + i] = b0 - b7; 
+pub fn fast_idct_into(f: &[i32;64], vals: &mut [u8;64]) {
+    fast_idct_plane(f, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn fast_idct_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut _f = load_f32(f);
+    fast_idct_inplace(&mut _f);
+    store_truncate(&_f, plane, offset, stride);
+}
+
+pub(crate) const AAN_SCALE: [f32;8] = [
+    0.35355338, 0.49039263, 0.46193975, 0.4157348,
+    0.35355338, 0.2777851, 0.19134171, 0.09754512];
+
+// coefficients in, spatial values (before level shift) out
+pub fn fast_idct_inplace(_f: &mut [f32;64]) {
+    fast_idct_kernel::<true, true>(_f);
+}
+
+// AAN with quantization folded into the prescale, see fast_prepare_quant
+// (the IJG ifast trick) both passes skip their s0..s7 multiplies
+pub fn fast_idct_dequant(f: &[i32;64], table: &[f32;64], vals: &mut [u8;64]) {
+    let mut _f = [0_f32;64];
+    for i in 0..64 {
+        _f[i] = f[i] as f32 * table[i];
     }
-    
+    fast_idct_kernel::<true, false>(&mut _f);
+    store_truncate(&_f, vals, 0, 8);
+}
+
+// one time per quantization table: q[v*8 + u] * s[u] * s[v]
+pub fn fast_prepare_quant(q: &[u16;64]) -> [f32;64] {
+    let mut table = [0_f32;64];
+    for i in 0..64 {
+        table[i] = q[i] as f32 * AAN_SCALE[i % 8] * AAN_SCALE[i / 8];
+    }
+    table
+}
+
+// one AAN 1D pass, f[k] is coefficient k, out[k] is sample k. PRESCALE
+// multiplies the inputs by AAN_SCALE, false when fast_prepare_quant has
+// folded it into the dequantize
+#[inline(always)]
+#[allow(clippy::excessive_precision, clippy::approx_constant)]
+pub(crate) fn aan_1d<V: Lanes, const PRESCALE: bool>(f: [V;8]) -> [V;8] {
+    let m0 = 1.847759_f32;
+    let m5 = 0.76536685_f32;
+    let m1 = V::splat(1.4142135);
+    let m2 = V::splat(m0 - m5);
+    let m3 = V::splat(1.4142135);
+    let m4 = V::splat(m0 + m5);
+    let m5 = V::splat(m5);
+    let g: [V;8] = core::array::from_fn(|k| if PRESCALE { f[k] * V::splat(AAN_SCALE[k]) } else { f[k] });
+
+    let f4 = g[5] - g[3];
+    let f5 = g[1] + g[7];
+    let f6 = g[1] - g[7];
+    let f7 = g[5] + g[3];
+
+    let e2 = g[2] - g[6];
+    let e3 = g[2] + g[6];
+    let e5 = f5 - f7;
+    let e7 = f5 + f7;
+    let e8 = f4 + f6;
+
+    let d2 = e2 * m1;
+    let d4 = f4 * m2;
+    let d5 = e5 * m3;
+    let d6 = f6 * m4;
+    let d8 = e8 * m5;
+
+    let c0 = g[0] + g[4];
+    let c1 = g[0] - g[4];
+    let c2 = d2 - e3;
+    let c4 = d4 + d8;
+    let c5 = d5 + e7;
+    let c6 = d6 - d8;
+    let c8 = c5 - c6;
+
+    let b0 = c0 + e3;
+    let b1 = c1 + c2;
+    let b2 = c1 - c2;
+    let b3 = c0 - e3;
+    let b4 = c4 - c8;
+    let b6 = c6 - e7;
+
+    [b0 + e7, b1 + b6, b2 + c8, b3 + b4, b3 - b4, b2 - c8, b1 - b6, b0 - e7]
+}
+
+#[inline(always)]
+pub(crate) fn fast_idct_kernel<const PRUNE: bool, const PRESCALE: bool>(_f: &mut [f32;64]) {
+    // columns
     for i in 0..8 {
-        let g0 = _f[i*8 + 0] as f32 * s0;
-        let g1 = _f[i*8 + 4] as f32 * s4;
-        let g2 = _f[i*8 + 2] as f32 * s2;
-        let g3 = _f[i*8 + 6] as f32 * s6;
-        let g4 = _f[i*8 + 5] as f32 * s5;
-        let g5 = _f[i*8 + 1] as f32 * s1;
-        let g6 = _f[i*8 + 7] as f32 * s7;
-        let g7 = _f[i*8 + 3] as f32 * s3;
-    
-        let f0 = g0;
-        let f1 = g1;
-        let f2 = g2;
-        let f3 = g3;
-        let f4 = g4 - g7;
-        let f5 = g5 + g6;
-        let f6 = g5 - g6;
-        let f7 = g4 + g7;
-    
-        let e0 = f0;
-        let e1 = f1;
-        let e2 = f2 - f3;
-        let e3 = f2 + f3;
-        let e4 = f4;
-        let e5 = f5 - f7;
-        let e6 = f6;
-        let e7 = f5 + f7;
-        let e8 = f4 + f6;
-    
-        let d0 = e0;
-        let d1 = e1;
-        let d2 = e2 * m1;
-        let d3 = e3;
-        let d4 = e4 * m2;
-        let d5 = e5 * m3;
-        let d6 = e6 * m4;
-        let d7 = e7;
-        let d8 = e8 * m5;
-    
-        let c0 = d0 + d1;
-        let c1 = d0 - d1;
-        let c2 = d2 - d3;
-        let c3 = d3;
-        let c4 = d4 + d8;
-        let c5 = d5 + d7;
-        let c6 = d6 - d8;
-        let c7 = d7;
-        let c8 = c5 - c6;
-    
-        let b0 = c0 + c3;
-        let b1 = c1 + c2;
-        let b2 = c1 - c2;
-        let b3 = c0 - c3;
-        let b4 = c4 - c8;
-        let b5 = c8;
-        let b6 = c6 - c7;
-        let b7 = c7;
-        
-        vals[i * 8 + 0] = ((b0 + b7 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 1] = ((b1 + b6 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 2] = ((b2 + b5 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 3] = ((b3 + b4 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 4] = ((b3 - b4 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 5] = ((b2 - b5 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 6] = ((b1 - b6 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 7] = ((b0 - b7 + 128.0) as i32).clamp(0,255) as u8;
+        if PRUNE && (1..8).all(|v| _f[v*8 + i] == 0.0) {
+            // AC terms all zero, every output is the prescaled DC term
+            let dc = if PRESCALE { _f[i] * AAN_SCALE[0] } else { _f[i] };
+            for v in 0..8 {
+                _f[v*8 + i] = dc;
+            }
+            continue;
+        }
+        let out = aan_1d::<f32, PRESCALE>(core::array::from_fn(|v| _f[v*8 + i]));
+        for (v, o) in out.into_iter().enumerate() {
+            _f[v*8 + i] = o;
+        }
+    }
+    // rows
+    for row in _f.chunks_exact_mut(8) {
+        let out = aan_1d::<f32, PRESCALE>(core::array::from_fn(|u| row[u]));
+        row.copy_from_slice(&out);
     }
-    vals.to_vec()
 }
 
 // AAN
 pub fn fast_idct_f64(f: &[i32]) -> Vec<u8> {
-    let mut _f  = [0_f64;64];
     let mut vals = [0_u8;64];
+    fast_idct_f64_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+pub fn fast_idct_f64_into(f: &[i32;64], vals: &mut [u8;64]) {
+    let mut _f = [0_f64;64];
+    for (d, s) in _f.iter_mut().zip(f.iter()) {
+        *d = *s as f64;
+    }
+    fast_idct_f64_inplace(&mut _f);
+    for (d, s) in vals.iter_mut().zip(_f.iter()) {
+        *d = ((*s + 128.0) as i32).clamp(0,255) as u8;
+    }
+}
+
+// coefficients in, spatial values (before level shift) out
+pub fn fast_idct_f64_inplace(_f: &mut [f64;64]) {
+    fast_idct_f64_kernel::<true>(_f);
+}
+
+#[allow(clippy::approx_constant, clippy::identity_op, clippy::erasing_op, clippy::unnecessary_cast)]
+fn fast_idct_f64_kernel<const PRUNE: bool>(_f: &mut [f64;64]) {
     let m0 = 1.847759_f64;
     let m1 = 1.4142135_f64;
     let m3 = 1.4142135_f64;
@@ -358,14 +450,22 @@ pub fn fast_idct_f64(f: &[i32]) -> Vec<u8> {
     let s7 = 0.09754512_f64;
     
     for i in 0..8 {
-        let g0 = f[0*8 + i] as f64 * s0;
-        let g1 = f[4*8 + i] as f64 * s4;
-        let g2 = f[2*8 + i] as f64 * s2;
-        let g3 = f[6*8 + i] as f64 * s6;
-        let g4 = f[5*8 + i] as f64 * s5;
-        let g5 = f[1*8 + i] as f64 * s1;
-        let g6 = f[7*8 + i] as f64 * s7;
-        let g7 = f[3*8 + i] as f64 * s3;
+        if PRUNE && (1..8).all(|v| _f[v*8 + i] == 0.0) {
+            // AC terms all zero, every output is the prescaled DC term
+            let dc = _f[i] * s0;
+            for v in 0..8 {
+                _f[v*8 + i] = dc;
+            }
+            continue;
+        }
+        let g0 = _f[0*8 + i] * s0;
+        let g1 = _f[4*8 + i] * s4;
+        let g2 = _f[2*8 + i] * s2;
+        let g3 = _f[6*8 + i] * s6;
+        let g4 = _f[5*8 + i] * s5;
+        let g5 = _f[1*8 + i] * s1;
+        let g6 = _f[7*8 + i] * s7;
+        let g7 = _f[3*8 + i] * s3;
     
         let f0 = g0;
         let f1 = g1;
@@ -483,18 +583,576 @@ pub fn fast_idct_f64(f: &[i32]) -> Vec<u8> {
         let b6 = c6 - c7;
         let b7 = c7;
         
-        vals[i * 8 + 0] = ((b0 + b7 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 1] = ((b1 + b6 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 2] = ((b2 + b5 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 3] = ((b3 + b4 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 4] = ((b3 - b4 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 5] = ((b2 - b5 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 6] = ((b1 - b6 + 128.0) as i32).clamp(0,255) as u8;
-        vals[i * 8 + 7] = ((b0 - b7 + 128.0) as i32).clamp(0,255) as u8;
+        _f[i * 8 + 0] = b0 + b7;
+        _f[i * 8 + 1] = b1 + b6;
+        _f[i * 8 + 2] = b2 + b5;
+        _f[i * 8 + 3] = b3 + b4;
+        _f[i * 8 + 4] = b3 - b4;
+        _f[i * 8 + 5] = b2 - b5;
+        _f[i * 8 + 6] = b1 - b6;
+        _f[i * 8 + 7] = b0 - b7;
+    }
+}
+
+// LL&M integer IDCT, port of IJG jidctint.c (jpeg_idct_islow, JDCT_ISLOW)
+// 13 bit constants, PASS1_BITS = 2 extra bits kept between the passes.
+// same rounding and range limit as libjpeg(-turbo), so the output is bit
+// exact with it for the same dequantized coefficients
+pub(crate) const CONST_BITS: i32 = 13;
+pub(crate) const PASS1_BITS: i32 = 2;
+
+pub(crate) const FIX_0_298631336: i32 = 2446;  // FIX(0.298631336)
+pub(crate) const FIX_0_390180644: i32 = 3196;  // FIX(0.390180644)
+pub(crate) const FIX_0_541196100: i32 = 4433;  // FIX(0.541196100)
+pub(crate) const FIX_0_765366865: i32 = 6270;  // FIX(0.765366865)
+pub(crate) const FIX_0_899976223: i32 = 7373;  // FIX(0.899976223)
+pub(crate) const FIX_1_175875602: i32 = 9633;  // FIX(1.175875602)
+pub(crate) const FIX_1_501321110: i32 = 12299; // FIX(1.501321110)
+pub(crate) const FIX_1_847759065: i32 = 15137; // FIX(1.847759065)
+pub(crate) const FIX_1_961570560: i32 = 16069; // FIX(1.961570560)
+pub(crate) const FIX_2_053119869: i32 = 16819; // FIX(2.053119869)
+pub(crate) const FIX_2_562915447: i32 = 20995; // FIX(2.562915447)
+pub(crate) const FIX_3_072711026: i32 = 25172; // FIX(3.072711026)
+
+// x / 2^n rounded, DESCALE in jdct.h
+#[inline(always)]
+pub(crate) fn descale(x: i32, n: i32) -> i32 {
+    (x + (1 << (n - 1))) >> n
+}
+
+// libjpeg IDCT_range_limit: x is the descaled value before the level shift,
+// the table is indexed with x & RANGE_MASK (1023) so far out of range values
+// wrap exactly like libjpeg does
+#[inline(always)]
+pub(crate) fn range_limit(x: i32) -> u8 {
+    let i = x & 1023;
+    if i < 128 {
+        (i + 128) as u8
+    } else if i < 512 {
+        255
+    } else if i < 896 {
+        0
+    } else {
+        (i - 896) as u8
+    }
+}
+
+pub fn islow_idct(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    islow_idct_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+pub fn islow_idct_into(f: &[i32;64], vals: &mut [u8;64]) {
+    islow_idct_plane(f, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn islow_idct_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    islow_idct_kernel::<false>(f, plane, offset, stride);
+}
+
+// descale in JLONG
+#[inline(always)]
+fn descale64(x: i64, n: i32) -> i64 {
+    (x + (1 << (n - 1))) >> n
+}
+
+// one jidctint 1D pass over d[0..8], outputs before the descale.
+// JLONG (64 bit) arithmetic as libjpeg-turbo on 64 bit targets: legal
+// input like 1023 * 255 in an AC position overflows 32 bits
+#[inline(always)]
+fn islow_1d(d: [i64;8]) -> [i64;8] {
+    // even part
+    let z2 = d[2];
+    let z3 = d[6];
+    let z1 = (z2 + z3) * FIX_0_541196100 as i64;
+    let tmp2 = z1 + z3 * -FIX_1_847759065 as i64;
+    let tmp3 = z1 + z2 * FIX_0_765366865 as i64;
+
+    let tmp0 = (d[0] + d[4]) << CONST_BITS;
+    let tmp1 = (d[0] - d[4]) << CONST_BITS;
+
+    let tmp10 = tmp0 + tmp3;
+    let tmp13 = tmp0 - tmp3;
+    let tmp11 = tmp1 + tmp2;
+    let tmp12 = tmp1 - tmp2;
+
+    // odd part
+    let tmp0 = d[7];
+    let tmp1 = d[5];
+    let tmp2 = d[3];
+    let tmp3 = d[1];
+
+    let z1 = tmp0 + tmp3;
+    let z2 = tmp1 + tmp2;
+    let z3 = tmp0 + tmp2;
+    let z4 = tmp1 + tmp3;
+    let z5 = (z3 + z4) * FIX_1_175875602 as i64; // sqrt(2) * c3
+
+    let tmp0 = tmp0 * FIX_0_298631336 as i64; // sqrt(2) * (-c1+c3+c5-c7)
+    let tmp1 = tmp1 * FIX_2_053119869 as i64; // sqrt(2) * ( c1+c3-c5+c7)
+    let tmp2 = tmp2 * FIX_3_072711026 as i64; // sqrt(2) * ( c1+c3+c5-c7)
+    let tmp3 = tmp3 * FIX_1_501321110 as i64; // sqrt(2) * ( c1+c3-c5-c7)
+    let z1 = z1 * -FIX_0_899976223 as i64;    // sqrt(2) * ( c7-c3)
+    let z2 = z2 * -FIX_2_562915447 as i64;    // sqrt(2) * (-c1-c3)
+    let z3 = z3 * -FIX_1_961570560 as i64 + z5; // sqrt(2) * (-c3-c5)
+    let z4 = z4 * -FIX_0_390180644 as i64 + z5; // sqrt(2) * ( c5-c3)
+
+    let tmp0 = tmp0 + z1 + z3;
+    let tmp1 = tmp1 + z2 + z4;
+    let tmp2 = tmp2 + z2 + z3;
+    let tmp3 = tmp3 + z1 + z4;
+
+    [tmp10 + tmp3, tmp11 + tmp2, tmp12 + tmp1, tmp13 + tmp0,
+     tmp13 - tmp0, tmp12 - tmp1, tmp11 - tmp2, tmp10 - tmp3]
+}
+
+// ZIGZAG: f is in zigzag order, the permutation is done by the pass 1 loads
+#[inline(always)]
+pub(crate) fn islow_idct_kernel<const ZIGZAG: bool>(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    // int workspace like libjpeg, `as i32` wraps as its (int) casts do
+    let mut ws = [0_i32;64];
+
+    // coefficient at natural index k
+    let c = |k: usize| coef::<ZIGZAG>(f, k) as i64;
+
+    // pass 1: columns from input, store into work array
+    // results are scaled up by sqrt(8) compared to a true IDCT
+    // and by 2^PASS1_BITS
+    for i in 0..8 {
+        if (1..8).all(|v| c(v*8 + i) == 0) {
+            // AC terms all zero
+            let dcval = (c(i) << PASS1_BITS) as i32;
+            for j in 0..8 {
+                ws[j*8 + i] = dcval;
+            }
+            continue;
+        }
+        let out = islow_1d(core::array::from_fn(|v| c(v*8 + i)));
+        for (v, val) in out.iter().enumerate() {
+            ws[v*8 + i] = descale64(*val, CONST_BITS - PASS1_BITS) as i32;
+        }
+    }
+
+    // pass 2: rows from work array, store into output
+    // the final descale removes PASS1_BITS and the factor of 8 (sqrt(8)^2)
+    for i in 0..8 {
+        let w = &ws[i*8..i*8 + 8];
+        let row = &mut plane[offset + i * stride..][..8];
+        if w[1..].iter().all(|v| *v == 0) {
+            // AC terms all zero
+            let outval = range_limit(descale64(w[0] as i64, PASS1_BITS + 3) as i32);
+            row.fill(outval);
+            continue;
+        }
+        let out = islow_1d(core::array::from_fn(|u| w[u] as i64));
+        for (d, val) in row.iter_mut().zip(out) {
+            *d = range_limit(descale64(val, CONST_BITS + PASS1_BITS + 3) as i32);
+        }
+    }
+}
+
+// AAN integer IDCT, port of IJG jidctfst.c (jpeg_idct_ifast, JDCT_IFAST)
+// 8 bit constants, products truncated like the default (not
+// USE_ACCURATE_ROUNDING) build and temporaries wrapped to 16 bit, so the
+// output is bit exact with libjpeg-turbo.
+// The AAN prescale lives in the dequantization table, see
+// ifast_prepare_quant, so there is only the dequantizing entry point: with
+// q = 1 the table keeps 2 fraction bits and the high frequencies scale to 0.
+const IFAST_SCALE_BITS: i32 = 2;
+
+// AAN scale factors * 2^14, aanscales in jddctmgr.c and jcdctmgr.c
+pub(crate) const AAN_SCALES: [i32;64] = [
+    16384, 22725, 21407, 19266, 16384, 12873,  8867,  4520,
+    22725, 31521, 29692, 26722, 22725, 17855, 12299,  6270,
+    21407, 29692, 27969, 25172, 21407, 16819, 11585,  5906,
+    19266, 26722, 25172, 22654, 19266, 15137, 10426,  5315,
+    16384, 22725, 21407, 19266, 16384, 12873,  8867,  4520,
+    12873, 17855, 16819, 15137, 12873, 10114,  6967,  3552,
+     8867, 12299, 11585, 10426,  8867,  6967,  4799,  2446,
+     4520,  6270,  5906,  5315,  4520,  3552,  2446,  1247];
+
+// one time per quantization table, as jddctmgr.c does for JDCT_IFAST:
+// q * aanscale, leaving IFAST_SCALE_BITS fraction bits. Truncated to 16
+// bits like libjpeg's IFAST_MULT_TYPE (short), so with 16 bit coefficients
+// the dequantize can't overflow for 16 bit tables either
+pub fn ifast_prepare_quant(q: &[u16;64]) -> [i32;64] {
+    let mut table = [0_i32;64];
+    for i in 0..64 {
+        table[i] = descale(q[i] as i32 * AAN_SCALES[i], 14 - IFAST_SCALE_BITS) as i16 as i32;
+    }
+    table
+}
+
+// f is the quantized coefficients, table from ifast_prepare_quant
+pub fn ifast_idct_dequant(f: &[i32;64], table: &[i32;64], vals: &mut [u8;64]) {
+    ifast_idct_dequant_plane(f, table, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn ifast_idct_dequant_plane(f: &[i32;64], table: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    ifast_idct_kernel::<false>(f, table, plane, offset, stride);
+}
+
+// ZIGZAG: f is in zigzag order, table in natural order
+#[inline(always)]
+#[allow(clippy::identity_op, clippy::erasing_op)]
+pub(crate) fn ifast_idct_kernel<const ZIGZAG: bool>(f: &[i32;64], table: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    const CONST_BITS: i32 = 8;
+    const PASS1_BITS: i32 = 2;
+    const FIX_1_082392200: i32 = 277; // FIX(1.082392200)
+    const FIX_1_414213562: i32 = 362; // FIX(1.414213562)
+    const FIX_1_847759065: i32 = 473; // FIX(1.847759065)
+    const FIX_2_613125930: i32 = 669; // FIX(2.613125930)
+
+    // libjpeg-turbo builds with DCTELEM = short, every temporary is
+    // stored as 16 bit while the arithmetic itself is done in int
+    #[inline(always)]
+    fn e(x: i32) -> i32 {
+        x as i16 as i32
+    }
+    #[inline(always)]
+    fn multiply(x: i32, c: i32) -> i32 {
+        e((x * c) >> CONST_BITS)
+    }
+
+    let mut ws = [0_i32;64];
+    // coefficient at natural index k
+    let c = |k: usize| coef::<ZIGZAG>(f, k);
+
+    // pass 1: columns from input, store into work array
+    for i in 0..8 {
+        if (1..8).all(|v| c(v*8 + i) == 0) {
+            // AC terms all zero
+            let dcval = c(i) * table[i];
+            for j in 0..8 {
+                ws[j*8 + i] = dcval;
+            }
+            continue;
+        }
+
+        // even part
+        let tmp0 = e(c(0*8 + i) * table[0*8 + i]);
+        let tmp1 = e(c(2*8 + i) * table[2*8 + i]);
+        let tmp2 = e(c(4*8 + i) * table[4*8 + i]);
+        let tmp3 = e(c(6*8 + i) * table[6*8 + i]);
+
+        let tmp10 = e(tmp0 + tmp2); // phase 3
+        let tmp11 = e(tmp0 - tmp2);
+
+        let tmp13 = e(tmp1 + tmp3); // phases 5-3
+        let tmp12 = e(multiply(tmp1 - tmp3, FIX_1_414213562) - tmp13); // 2*c4
+
+        let tmp0 = e(tmp10 + tmp13); // phase 2
+        let tmp3 = e(tmp10 - tmp13);
+        let tmp1 = e(tmp11 + tmp12);
+        let tmp2 = e(tmp11 - tmp12);
+
+        // odd part
+        let tmp4 = e(c(1*8 + i) * table[1*8 + i]);
+        let tmp5 = e(c(3*8 + i) * table[3*8 + i]);
+        let tmp6 = e(c(5*8 + i) * table[5*8 + i]);
+        let tmp7 = e(c(7*8 + i) * table[7*8 + i]);
+
+        let z13 = e(tmp6 + tmp5); // phase 6
+        let z10 = e(tmp6 - tmp5);
+        let z11 = e(tmp4 + tmp7);
+        let z12 = e(tmp4 - tmp7);
+
+        let tmp7 = e(z11 + z13); // phase 5
+        let tmp11 = multiply(z11 - z13, FIX_1_414213562); // 2*c4
+
+        let z5 = multiply(z10 + z12, FIX_1_847759065); // 2*c2
+        let tmp10 = e(multiply(z12, FIX_1_082392200) - z5); // 2*(c2-c6)
+        let tmp12 = e(multiply(z10, -FIX_2_613125930) + z5); // -2*(c2+c6)
+
+        let tmp6 = e(tmp12 - tmp7); // phase 2
+        let tmp5 = e(tmp11 - tmp6);
+        let tmp4 = e(tmp10 + tmp5);
+
+        ws[0*8 + i] = tmp0 + tmp7;
+        ws[7*8 + i] = tmp0 - tmp7;
+        ws[1*8 + i] = tmp1 + tmp6;
+        ws[6*8 + i] = tmp1 - tmp6;
+        ws[2*8 + i] = tmp2 + tmp5;
+        ws[5*8 + i] = tmp2 - tmp5;
+        ws[4*8 + i] = tmp3 + tmp4;
+        ws[3*8 + i] = tmp3 - tmp4;
+    }
+
+    // pass 2: rows from work array, store into output
+    for i in 0..8 {
+        let w = &ws[i*8..i*8 + 8];
+        let row = &mut plane[offset + i * stride..][..8];
+        if w[1] == 0 && w[2] == 0 && w[3] == 0 && w[4] == 0
+            && w[5] == 0 && w[6] == 0 && w[7] == 0 {
+            // AC terms all zero
+            let outval = range_limit(w[0] >> (PASS1_BITS + 3));
+            row.fill(outval);
+            continue;
+        }
+
+        // even part
+        let tmp10 = e(e(w[0]) + e(w[4]));
+        let tmp11 = e(e(w[0]) - e(w[4]));
+
+        let tmp13 = e(e(w[2]) + e(w[6]));
+        let tmp12 = e(multiply(e(w[2]) - e(w[6]), FIX_1_414213562) - tmp13);
+
+        let tmp0 = e(tmp10 + tmp13);
+        let tmp3 = e(tmp10 - tmp13);
+        let tmp1 = e(tmp11 + tmp12);
+        let tmp2 = e(tmp11 - tmp12);
+
+        // odd part
+        let z13 = e(e(w[5]) + e(w[3]));
+        let z10 = e(e(w[5]) - e(w[3]));
+        let z11 = e(e(w[1]) + e(w[7]));
+        let z12 = e(e(w[1]) - e(w[7]));
+
+        let tmp7 = e(z11 + z13);
+        let tmp11 = multiply(z11 - z13, FIX_1_414213562);
+
+        let z5 = multiply(z10 + z12, FIX_1_847759065);
+        let tmp10 = e(multiply(z12, FIX_1_082392200) - z5);
+        let tmp12 = e(multiply(z10, -FIX_2_613125930) + z5);
+
+        let tmp6 = e(tmp12 - tmp7);
+        let tmp5 = e(tmp11 - tmp6);
+        let tmp4 = e(tmp10 + tmp5);
+
+        let shift = PASS1_BITS + 3;
+        row[0] = range_limit((tmp0 + tmp7) >> shift);
+        row[7] = range_limit((tmp0 - tmp7) >> shift);
+        row[1] = range_limit((tmp1 + tmp6) >> shift);
+        row[6] = range_limit((tmp1 - tmp6) >> shift);
+        row[2] = range_limit((tmp2 + tmp5) >> shift);
+        row[5] = range_limit((tmp2 - tmp5) >> shift);
+        row[4] = range_limit((tmp3 + tmp4) >> shift);
+        row[3] = range_limit((tmp3 - tmp4) >> shift);
+    }
+}
+
+// reduced size IDCTs, port of IJG jidctred.c (jpeg_idct_4x4, 2x2, 1x1)
+// same 64 dequantized coefficients in, an NxN block out for 1/2, 1/4 and
+// 1/8 scaled decoding. Only the low frequency rows and columns are used,
+// output is bit exact with libjpeg-turbo.
+const FIX_0_211164243: i32 = 1730;  // FIX(0.211164243)
+const FIX_0_509795579: i32 = 4176;  // FIX(0.509795579)
+const FIX_0_601344887: i32 = 4926;  // FIX(0.601344887)
+const FIX_0_720959822: i32 = 5906;  // FIX(0.720959822)
+const FIX_0_850430095: i32 = 6967;  // FIX(0.850430095)
+const FIX_1_061594337: i32 = 8697;  // FIX(1.061594337)
+const FIX_1_272758580: i32 = 10426; // FIX(1.272758580)
+const FIX_1_451774981: i32 = 11893; // FIX(1.451774981)
+const FIX_2_172734803: i32 = 17799; // FIX(2.172734803)
+const FIX_3_624509785: i32 = 29692; // FIX(3.624509785)
+
+pub fn idct_4x4(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;16];
+    idct_4x4_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+pub fn idct_4x4_into(f: &[i32;64], vals: &mut [u8;16]) {
+    idct_4x4_plane(f, vals, 0, 4);
+}
+
+// row y of the 4x4 block is written to plane[offset + y * stride..][..4]
+// JLONG intermediates and an int workspace as islow_idct_kernel
+#[allow(clippy::identity_op, clippy::erasing_op)]
+pub fn idct_4x4_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ws = [0_i32;32];
+    let c = |k: usize| f[k] as i64;
+
+    // pass 1: columns from input, 4 rows into work array
+    for i in 0..8 {
+        // column 4 is not used by pass 2
+        if i == 4 {
+            continue;
+        }
+        if f[1*8 + i] == 0 && f[2*8 + i] == 0 && f[3*8 + i] == 0
+            && f[5*8 + i] == 0 && f[6*8 + i] == 0 && f[7*8 + i] == 0 {
+            // AC terms all zero (row 4 does not contribute)
+            let dcval = (c(i) << PASS1_BITS) as i32;
+            for j in 0..4 {
+                ws[j*8 + i] = dcval;
+            }
+            continue;
+        }
+        let out = idct_4_1d(c(i), c(2*8 + i), c(6*8 + i), c(7*8 + i), c(5*8 + i), c(3*8 + i), c(1*8 + i));
+        for (j, val) in out.iter().enumerate() {
+            ws[j*8 + i] = descale64(*val, CONST_BITS - PASS1_BITS + 1) as i32;
+        }
+    }
+
+    // pass 2: 4 rows from work array, 4 samples each
+    for i in 0..4 {
+        let w = &ws[i*8..i*8 + 8];
+        let row = &mut plane[offset + i * stride..][..4];
+        if w[1] == 0 && w[2] == 0 && w[3] == 0 && w[5] == 0 && w[6] == 0 && w[7] == 0 {
+            // AC terms all zero
+            let outval = range_limit(descale64(w[0] as i64, PASS1_BITS + 3) as i32);
+            row.fill(outval);
+            continue;
+        }
+        let w = |u: usize| w[u] as i64;
+        let out = idct_4_1d(w(0), w(2), w(6), w(7), w(5), w(3), w(1));
+        for (d, val) in row.iter_mut().zip(out) {
+            *d = range_limit(descale64(val, CONST_BITS + PASS1_BITS + 3 + 1) as i32);
+        }
     }
+}
+
+// one jidctred 4 point pass from inputs 0, 2, 6 and the odd 7, 5, 3, 1,
+// outputs before the descale
+#[inline(always)]
+fn idct_4_1d(d0: i64, d2: i64, d6: i64, z1: i64, z2: i64, z3: i64, z4: i64) -> [i64;4] {
+    // even part
+    let tmp0 = d0 << (CONST_BITS + 1);
+    let tmp2 = d2 * FIX_1_847759065 as i64 + d6 * -FIX_0_765366865 as i64;
+
+    let tmp10 = tmp0 + tmp2;
+    let tmp12 = tmp0 - tmp2;
+
+    // odd part
+    let tmp0 = z1 * -FIX_0_211164243 as i64 // sqrt(2) * (c3-c1)
+        + z2 * FIX_1_451774981 as i64      // sqrt(2) * (c3+c7)
+        + z3 * -FIX_2_172734803 as i64     // sqrt(2) * (-c1-c5)
+        + z4 * FIX_1_061594337 as i64;     // sqrt(2) * (c5+c7)
+
+    let tmp2 = z1 * -FIX_0_509795579 as i64 // sqrt(2) * (c7-c5)
+        + z2 * -FIX_0_601344887 as i64     // sqrt(2) * (c5-c1)
+        + z3 * FIX_0_899976223 as i64      // sqrt(2) * (c3-c7)
+        + z4 * FIX_2_562915447 as i64;     // sqrt(2) * (c1+c3)
+
+    [tmp10 + tmp2, tmp12 + tmp0, tmp12 - tmp0, tmp10 - tmp2]
+}
+
+pub fn idct_2x2(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;4];
+    idct_2x2_into(block(f), &mut vals);
     vals.to_vec()
 }
 
+pub fn idct_2x2_into(f: &[i32;64], vals: &mut [u8;4]) {
+    idct_2x2_plane(f, vals, 0, 2);
+}
+
+// row y of the 2x2 block is written to plane[offset + y * stride..][..2]
+// JLONG intermediates and an int workspace as islow_idct_kernel
+#[allow(clippy::identity_op)]
+pub fn idct_2x2_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ws = [0_i32;16];
+    let c = |k: usize| f[k] as i64;
+
+    // pass 1: columns from input, 2 rows into work array
+    for i in 0..8 {
+        // columns 2, 4 and 6 are not used by pass 2
+        if i == 2 || i == 4 || i == 6 {
+            continue;
+        }
+        if f[1*8 + i] == 0 && f[3*8 + i] == 0 && f[5*8 + i] == 0 && f[7*8 + i] == 0 {
+            // odd AC terms all zero
+            let dcval = (c(i) << PASS1_BITS) as i32;
+            ws[i] = dcval;
+            ws[8 + i] = dcval;
+            continue;
+        }
+        let [out0, out1] = idct_2_1d(c(i), c(7*8 + i), c(5*8 + i), c(3*8 + i), c(1*8 + i));
+        let shift = CONST_BITS - PASS1_BITS + 2;
+        ws[i] = descale64(out0, shift) as i32;
+        ws[8 + i] = descale64(out1, shift) as i32;
+    }
+
+    // pass 2: 2 rows from work array, 2 samples each
+    for i in 0..2 {
+        let w = &ws[i*8..i*8 + 8];
+        let row = &mut plane[offset + i * stride..][..2];
+        if w[1] == 0 && w[3] == 0 && w[5] == 0 && w[7] == 0 {
+            // odd AC terms all zero
+            let outval = range_limit(descale64(w[0] as i64, PASS1_BITS + 3) as i32);
+            row.fill(outval);
+            continue;
+        }
+        let w = |u: usize| w[u] as i64;
+        let [out0, out1] = idct_2_1d(w(0), w(7), w(5), w(3), w(1));
+        let shift = CONST_BITS + PASS1_BITS + 3 + 2;
+        row[0] = range_limit(descale64(out0, shift) as i32);
+        row[1] = range_limit(descale64(out1, shift) as i32);
+    }
+}
+
+// one jidctred 2 point pass from input 0 and the odd 7, 5, 3, 1, outputs
+// before the descale
+#[inline(always)]
+fn idct_2_1d(d0: i64, d7: i64, d5: i64, d3: i64, d1: i64) -> [i64;2] {
+    // even part
+    let tmp10 = d0 << (CONST_BITS + 2);
+
+    // odd part
+    let tmp0 = d7 * -FIX_0_720959822 as i64 // sqrt(2) * (c7-c5+c3-c1)
+        + d5 * FIX_0_850430095 as i64      // sqrt(2) * (-c1+c3+c5+c7)
+        + d3 * -FIX_1_272758580 as i64     // sqrt(2) * (-c1+c3-c5-c7)
+        + d1 * FIX_3_624509785 as i64;     // sqrt(2) * (c1+c3+c5+c7)
+
+    [tmp10 + tmp0, tmp10 - tmp0]
+}
+
+pub fn idct_1x1(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;1];
+    idct_1x1_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+// DC only: the block average
+pub fn idct_1x1_into(f: &[i32;64], vals: &mut [u8;1]) {
+    vals[0] = range_limit(descale(f[0], 3));
+}
+
+pub fn idct_1x1_plane(f: &[i32;64], plane: &mut [u8], offset: usize, _stride: usize) {
+    plane[offset] = range_limit(descale(f[0], 3));
+}
+
+fn block(f: &[i32]) -> &[i32;64] {
+    f[..64].try_into().unwrap()
+}
+
+// coefficient at natural index i of f, f in zigzag order when ZIGZAG
+#[inline(always)]
+fn coef<const ZIGZAG: bool>(f: &[i32;64], i: usize) -> i32 {
+    if ZIGZAG { f[ZIGZAG_ORDER[i]] } else { f[i] }
+}
+
+fn load_f32(f: &[i32;64]) -> [f32;64] {
+    let mut ff = [0_f32;64];
+    for (d, s) in ff.iter_mut().zip(f.iter()) {
+        *d = *s as f32;
+    }
+    ff
+}
+
+// level shift and clamp, fraction dropped by `as`
+// row y of the block goes to plane[offset + y * stride..][..8]
+pub(crate) fn store_truncate(ff: &[f32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    for (y, src) in ff.chunks_exact(8).enumerate() {
+        let row = &mut plane[offset + y * stride..][..8];
+        for (d, s) in row.iter_mut().zip(src.iter()) {
+            *d = ((*s + 128.0) as i32).clamp(0,255) as u8;
+        }
+    }
+}
+
+// level shift and clamp, rounded to nearest
+pub(crate) fn store_round(ff: &[f32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    for (y, src) in ff.chunks_exact(8).enumerate() {
+        let row = &mut plane[offset + y * stride..][..8];
+        for (d, s) in row.iter_mut().zip(src.iter()) {
+            *d = (s.round() as isize + 128).clamp(0,255) as u8;
+        }
+    }
+}
 
 pub fn print_vec(f:&[u8]) -> String {
     let mut str = "".to_string();
@@ -507,6 +1165,7 @@ pub fn print_vec(f:&[u8]) -> String {
 }
 
 // method1 https://note.com/mith_mmk/n/n6f57f007453b
+#[allow(dead_code, clippy::assign_op_pattern)]
 fn idct1(f :&[i32]) -> Vec<u8> {
     let vals :Vec<u8> = (0..64).map(|i| {
         let (x,y) = ((i%8) as f32,(i/8) as f32);
@@ -567,6 +1226,7 @@ fn idct2 (f :&[i32]) -> Vec<u8> {
 
 // method3 https://note.com/mith_mmk/n/n6f57f007453b
 // normalize matrix multiply
+#[allow(dead_code, clippy::excessive_precision, clippy::approx_constant, clippy::assign_op_pattern)]
 fn idct3 (f :&[i32]) -> Vec<u8> {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
@@ -596,6 +1256,7 @@ fn idct3 (f :&[i32]) -> Vec<u8> {
 }
 
 // method4 https://note.com/mith_mmk/n/n6f57f007453b
+#[allow(dead_code, clippy::excessive_precision, clippy::approx_constant, clippy::assign_op_pattern)]
 fn idct4 (f :&[i32]) -> Vec<u8> {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
@@ -628,6 +1289,7 @@ fn idct4 (f :&[i32]) -> Vec<u8> {
 
 // method5 https://note.com/mith_mmk/n/n6f57f007453b
 // fixed number
+#[allow(dead_code, clippy::assign_op_pattern)]
 fn idct5 (f :&[i32]) -> Vec<u8> {
     let c_table :[[i32;8];8] = // * 256 << 8 
        [[ 181,  251,  237,  213,  181,  142,   98,   50],
@@ -660,6 +1322,7 @@ fn idct5 (f :&[i32]) -> Vec<u8> {
 
 // method6 https://note.com/mith_mmk/n/n6f57f007453b
 // extend loop
+#[allow(dead_code, clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op, clippy::assign_op_pattern)]
 fn idct6 (f :&[i32]) -> Vec<u8> {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
@@ -698,6 +1361,13 @@ fn idct6 (f :&[i32]) -> Vec<u8> {
 // method7 https://note.com/mith_mmk/n/n6f57f007453b
 // use matrix symmetry X
 pub fn idct7 (f :&[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    idct7_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+#[allow(clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op, clippy::assign_op_pattern)]
+pub fn idct7_into (f :&[i32;64], vals :&mut [u8;64]) {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
     [ 0.70710678,  0.83146961,  0.38268343, -0.19509032, -0.70710678, -0.98078528, -0.92387953, -0.55557023],
@@ -707,7 +1377,6 @@ pub fn idct7 (f :&[i32]) -> Vec<u8> {
     [ 0.70710678, -0.55557023, -0.38268343,  0.98078528, -0.70710678, -0.19509032,  0.92387953, -0.83146961],
     [ 0.70710678, -0.83146961,  0.38268343,  0.19509032, -0.70710678, 0.98078528, -0.92387953,  0.55557023],
     [ 0.70710678, -0.98078528,  0.92387953, -0.83146961,  0.70710678, -0.55557023,  0.38268343, -0.19509032]];
-    let mut vals :Vec<u8> = (0..64).map(|_| 0).collect();
     for i in 0..32 {
         let (x,y) = ((i%4) as usize,(i/4) as usize);
         // IDCT from CCITT Rec. T.81 (1992 E) p.27 A3.3
@@ -738,12 +1407,18 @@ pub fn idct7 (f :&[i32]) -> Vec<u8> {
         let v = val2.round() as isize + 128 ;
         vals[y *8 + 7-x] = if v < 0 {0} else if v > 255 {255} else {v as u8};
     }
-    vals
 }
 
 // method8 https://note.com/mith_mmk/n/n6f57f007453b
 // use matrix symmetry X,Y
 pub fn idct8 (f :&[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    idct8_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+#[allow(clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op)]
+pub fn idct8_into (f :&[i32;64], vals :&mut [u8;64]) {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
     [ 0.70710678,  0.83146961,  0.38268343, -0.19509032, -0.70710678, -0.98078528, -0.92387953, -0.55557023],
@@ -753,7 +1428,6 @@ pub fn idct8 (f :&[i32]) -> Vec<u8> {
     [ 0.70710678, -0.55557023, -0.38268343,  0.98078528, -0.70710678, -0.19509032,  0.92387953, -0.83146961],
     [ 0.70710678, -0.83146961,  0.38268343,  0.19509032, -0.70710678, 0.98078528, -0.92387953,  0.55557023],
     [ 0.70710678, -0.98078528,  0.92387953, -0.83146961,  0.70710678, -0.55557023,  0.38268343, -0.19509032]];
-    let mut vals :Vec<u8> = (0..64).map(|_| 0).collect();
     for i in 0..16 {
         let (x,y) = ((i%4) as usize,(i/4) as usize);
         // IDCT from CCITT Rec. T.81 (1992 E) p.27 A3.3
@@ -810,12 +1484,18 @@ pub fn idct8 (f :&[i32]) -> Vec<u8> {
         let v = val22.round() as isize + 128 ;
         vals[(7 - y) *8 + 7-x] = if v < 0 {0} else if v > 255 {255} else {v as u8};
     }
-    vals
 }
 
 // method9 https://note.com/mith_mmk/n/n6f57f007453b
 // use calculate same time
 pub fn idct9 (f :&[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    idct9_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+#[allow(clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op)]
+pub fn idct9_into (f :&[i32;64], vals :&mut [u8;64]) {
     let c_table :[[f32;8];8] = 
     [[ 0.70710678,  0.98078528,  0.92387953,  0.83146961,  0.70710678, 0.55557023,  0.38268343,  0.19509032],
     [ 0.70710678,  0.83146961,  0.38268343, -0.19509032, -0.70710678, -0.98078528, -0.92387953, -0.55557023],
@@ -825,7 +1505,6 @@ pub fn idct9 (f :&[i32]) -> Vec<u8> {
     [ 0.70710678, -0.55557023, -0.38268343,  0.98078528, -0.70710678, -0.19509032,  0.92387953, -0.83146961],
     [ 0.70710678, -0.83146961,  0.38268343,  0.19509032, -0.70710678, 0.98078528, -0.92387953,  0.55557023],
     [ 0.70710678, -0.98078528,  0.92387953, -0.83146961,  0.70710678, -0.55557023,  0.38268343, -0.19509032]];
-    let mut vals :Vec<u8> = (0..64).map(|_| 0).collect();
     for i in 0..16 {
         let (x,y) = ((i%4) as usize,(i/4) as usize);
         // IDCT from CCITT Rec. T.81 (1992 E) p.27 A3.3
@@ -838,8 +1517,8 @@ pub fn idct9 (f :&[i32]) -> Vec<u8> {
             let temp1 = f[0*8 + u] as f32 * c_table[y][0] + f[2*8 + u] as f32 * c_table[y][2]
                       + f[4*8 + u] as f32 * c_table[y][4] + f[6*8 + u] as f32 * c_table[y][6];
 
-            let temp2 = f[1*8 + u] as f32 * c_table[y][0] + f[3*8 + u] as f32 * c_table[y][2]
-                      + f[5*8 + u] as f32 * c_table[y][4] + f[7*8 + u] as f32 * c_table[y][6];
+            let temp2 = f[1*8 + u] as f32 * c_table[y][1] + f[3*8 + u] as f32 * c_table[y][3]
+                      + f[5*8 + u] as f32 * c_table[y][5] + f[7*8 + u] as f32 * c_table[y][7];
 
             let uval1 = temp1 + temp2;
             let uval2 = temp1 - temp2;
@@ -865,15 +1544,31 @@ pub fn idct9 (f :&[i32]) -> Vec<u8> {
         let v = val22.round() as isize + 128 ;
         vals[(7 - y) *8 + 7-x] = if v < 0 {0} else if v > 255 {255} else {v as u8};
     }
-    vals
 }
 
 // AP-922 method10 https://note.com/mith_mmk/n/n6f57f007453b
 pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
-    let g4 = 0.707106781186548 as f32;
-    let g:[[f32;7];4]  = [
+    let mut vals = [0_u8;64];
+    ap922_idct_into(block(f), &mut vals);
+    vals.to_vec()
+}
+
+pub fn ap922_idct_into(f :&[i32;64], vals :&mut [u8;64]) {
+    ap922_idct_plane(f, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn ap922_idct_plane(f :&[i32;64], plane :&mut [u8], offset :usize, stride :usize) {
+    let mut ff = load_f32(f);
+    ap922_idct_inplace(&mut ff);
+    store_round(&ff, plane, offset, stride);
+}
+
+// row scale g[u] * 0.25 * g[row] of the first pass, g[k] = cos(kπ/16)
+#[allow(clippy::excessive_precision)]
+const AP922_G: [[f32;7];4] = [
     /* row 0, 4 */
-      [
+    [
         0.1733799806652680, /* g1 * 0.25 * g4 */
         0.1633203706095470, /* g2 * 0.25 * g4 */
         0.1469844503024200, /* g3 * 0.25 * g4 */
@@ -881,9 +1576,9 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
         0.0982118697983878, /* g5 * 0.25 * g4 */
         0.0676495125182746, /* g6 * 0.25 * g4 */
         0.0344874224103679, /* g7 * 0.25 * g4 */
-      ],
-      /* row 1, 7 */
-      [
+    ],
+    /* row 1, 7 */
+    [
         0.2404849415639110, /* g1 * 0.25 * g1 */
         0.2265318615882220, /* g2 * 0.25 * g1 */
         0.2038732892122290, /* g3 * 0.25 * g1 */
@@ -891,9 +1586,9 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
         0.1362237766939550, /* g5 * 0.25 * g1 */
         0.0938325693794663, /* g6 * 0.25 * g1 */
         0.0478354290456362, /* g7 * 0.25 * g1 */
-      ],
-      /* row 2, 6 */
-      [
+    ],
+    /* row 2, 6 */
+    [
         0.2265318615882220, /* g1 * 0.25 * g2 */
         0.2133883476483180, /* g2 * 0.25 * g2 */
         0.1920444391778540, /* g3 * 0.25 * g2 */
@@ -901,9 +1596,9 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
         0.1283199917898340, /* g5 * 0.25 * g2 */
         0.0883883476483185, /* g6 * 0.25 * g2 */
         0.0450599888754343, /* g7 * 0.25 * g2 */
-      ],
-      /* row 3, 5 */
-      [
+    ],
+    /* row 3, 5 */
+    [
         0.2038732892122290, /* g1 * 0.25 * g3 */
         0.1920444391778540, /* g2 * 0.25 * g3 */
         0.1728354290456360, /* g3 * 0.25 * g3 */
@@ -911,29 +1606,49 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
         0.1154849415639110, /* g5 * 0.25 * g3 */
         0.0795474112858021, /* g6 * 0.25 * g3 */
         0.0405529186026822, /* g7 * 0.25 * g3 */
-      ]];
-    let t:[f32;3] = [
-        0.414213562373095  /* t1 = g6/g2 */,
-        0.198912367379658 /* t2 = g7/g1 */,
-        0.668178637919299 /* t3 = g5/g3 */,
-    ];
-    
+    ]];
+
+// tangents of the second pass
+#[allow(clippy::excessive_precision)]
+const AP922_T: [f32;3] = [
+    0.414213562373095 /* t1 = g6/g2 */,
+    0.198912367379658 /* t2 = g7/g1 */,
+    0.668178637919299 /* t3 = g5/g3 */,
+];
+
+// coefficients in, spatial values (before level shift) out
+pub fn ap922_idct_inplace(ff :&mut [f32;64]) {
+    ap922_idct_kernel::<true>(ff);
+}
+
+#[allow(clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op, clippy::unnecessary_cast)]
+fn ap922_idct_kernel<const PRUNE :bool>(ff :&mut [f32;64]) {
+    let g4 = 0.707106781186548 as f32;
+    let g = AP922_G;
+    let t = AP922_T;
+
     let row2idx = [0,1,2,3,0,3,2,1];
-    let mut _f = [[0_f32;8];8];
-    let mut vals :Vec<u8> = (0..64).map(|_| 0).collect();
 
     for i in 0..8 {
         let idx = row2idx[i];
+        if PRUNE && (1..8).all(|v| ff[v*8+i] == 0.0) {
+            // AC terms all zero, every output is the scaled DC term
+            let dc = ff[i] * g[idx][3];
+            for v in 0..8 {
+                ff[v*8+i] = dc;
+            }
+            continue;
+        }
         /* P */
         let p = [
-                f[0 +i] as f32,  /* 1 0 0 0 0 0 0 0 */
-                f[2*8+i] as f32,  /* 0 0 1 0 0 0 0 0 */
-                f[4*8+i] as f32,  /* 0 0 0 0 1 0 0 0 */
-                f[6*8+i] as f32,  /* 0 0 0 0 0 0 1 0 */
-                f[1*8+i] as f32,  /* 0 1 0 0 0 0 0 0 */
-                f[3*8+i] as f32,  /* 0 0 0 1 0 0 0 0 */
-                f[5*8+i] as f32,  /* 0 0 0 0 0 1 0 0 */
-                f[7*8+i] as f32];  /* 0 0 0 0 0 0 0 1 */
+                ff[0 +i],  /* 1 0 0 0 0 0 0 0 */
+                ff[2*8+i],  /* 0 0 1 0 0 0 0 0 */
+                ff[4*8+i],  /* 0 0 0 0 1 0 0 0 */
+                ff[6*8+i],  /* 0 0 0 0 0 0 1 0 */
+                ff[1*8+i],  /* 0 1 0 0 0 0 0 0 */
+                ff[3*8+i],  /* 0 0 0 1 0 0 0 0 */
+                ff[5*8+i],  /* 0 0 0 0 0 1 0 0 */
+                ff[7*8+i]];  /* 0 0 0 0 0 0 0 1 */
         let tmp = [
             p[0] * g[idx][3],
             p[1] * g[idx][1],
@@ -953,14 +1668,14 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
             p[4] * g[idx][6] - p[5] * g[idx][4] + p[6] * g[idx][2] - p[7] * g[idx][0], /*  0  0  0  0  g7 -g5  g3 -g1 */
         ];
         /* A */
-        _f[0][i]  = m[0] + m[4];  /*  1  0  0  0  1  0  0  0 */
-        _f[1][i]  = m[1] + m[5];  /*  0  1  0  0  0  1  0  0 */
-        _f[2][i]  = m[2] + m[6];  /*  0  0  1  0  0  0  1  0 */
-        _f[3][i]  = m[3] + m[7];  /*  0  0  0  1  0  0  0  1 */
-        _f[4][i]  = m[3] - m[7];  /*  0  0  0  1  0  0  0 -1 */
-        _f[5][i]  = m[2] - m[6];  /*  0  0  1  0  0  0 -1  0 */
-        _f[6][i]  = m[1] - m[5];  /*  0  1  0  0  0 -1  0  0 */
-        _f[7][i]  = m[0] - m[4];  /*  1  0  0  0 -1  0  0  0 */
+        ff[0*8+i]  = m[0] + m[4];  /*  1  0  0  0  1  0  0  0 */
+        ff[1*8+i]  = m[1] + m[5];  /*  0  1  0  0  0  1  0  0 */
+        ff[2*8+i]  = m[2] + m[6];  /*  0  0  1  0  0  0  1  0 */
+        ff[3*8+i]  = m[3] + m[7];  /*  0  0  0  1  0  0  0  1 */
+        ff[4*8+i]  = m[3] - m[7];  /*  0  0  0  1  0  0  0 -1 */
+        ff[5*8+i]  = m[2] - m[6];  /*  0  0  1  0  0  0 -1  0 */
+        ff[6*8+i]  = m[1] - m[5];  /*  0  1  0  0  0 -1  0  0 */
+        ff[7*8+i]  = m[0] - m[4];  /*  1  0  0  0 -1  0  0  0 */
       }
     
       // column
@@ -970,14 +1685,14 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
       for i in 0..8 {
         /* P */
         let p = [
-            _f[i][0],  /* 1 0 0 0 0 0 0 0 */
-            _f[i][2],  /* 0 0 1 0 0 0 0 0 */
-            _f[i][4],  /* 0 0 0 0 1 0 0 0 */
-            _f[i][6],  /* 0 0 0 0 0 0 1 0 */
-            _f[i][1],  /* 0 1 0 0 0 0 0 0 */
-            _f[i][3],  /* 0 0 0 1 0 0 0 0 */
-            _f[i][5],  /* 0 0 0 0 0 1 0 0 */
-            _f[i][7],  /* 0 0 0 0 0 0 0 1 */
+            ff[i*8+0],  /* 1 0 0 0 0 0 0 0 */
+            ff[i*8+2],  /* 0 0 1 0 0 0 0 0 */
+            ff[i*8+4],  /* 0 0 0 0 1 0 0 0 */
+            ff[i*8+6],  /* 0 0 0 0 0 0 1 0 */
+            ff[i*8+1],  /* 0 1 0 0 0 0 0 0 */
+            ff[i*8+3],  /* 0 0 0 1 0 0 0 0 */
+            ff[i*8+5],  /* 0 0 0 0 0 1 0 0 */
+            ff[i*8+7],  /* 0 0 0 0 0 0 0 1 */
         ];
         /* D */
         /* g4  0  0  0  0  0  0  0 */
@@ -1033,23 +1748,625 @@ pub fn ap922_idct(f :&[i32]) -> Vec<u8> {
             e[7],               /* 0  0  0  0  0  0  0  1 */
         ];
         /* A */
-        let v = (_f[0] + _f[4]).round()  as isize + 128;
-        vals[i*8+0]  = v.clamp(0,255) as u8;
-        let v = (_f[1] + _f[5]).round()  as isize + 128;
-        vals[i*8+1]  = v.clamp(0,255) as u8;
-        let v = (_f[2] + _f[6]).round()  as isize + 128;
-        vals[i*8+2]  = v.clamp(0,255) as u8;
-        let v = (_f[3] + _f[7]).round()  as isize + 128;
-        vals[i*8+3]  = v.clamp(0,255) as u8;
-        let v = (_f[3] - _f[7]).round()  as isize + 128;
-        vals[i*8+4]  = v.clamp(0,255) as u8;
-        let v = (_f[2] - _f[6]).round()  as isize + 128;
-        vals[i*8+5]  = v.clamp(0,255) as u8;
-        let v = (_f[1] - _f[5]).round()  as isize + 128;
-        vals[i*8+6]  = v.clamp(0,255) as u8;
-        let v = (_f[0] - _f[4]).round()  as isize + 128;
-        vals[i*8+7]  = v.clamp(0,255) as u8;
+        ff[i*8+0]  = _f[0] + _f[4];
+        ff[i*8+1]  = _f[1] + _f[5];
+        ff[i*8+2]  = _f[2] + _f[6];
+        ff[i*8+3]  = _f[3] + _f[7];
+        ff[i*8+4]  = _f[3] - _f[7];
+        ff[i*8+5]  = _f[2] - _f[6];
+        ff[i*8+6]  = _f[1] - _f[5];
+        ff[i*8+7]  = _f[0] - _f[4];
     }
+}
 
-    vals
-}
\ No newline at end of file
+// AP922 with an end of block hint, see llm_idct_eob
+// output is bit identical to ap922_idct_into
+pub fn ap922_idct_eob(f :&[i32;64], eob :usize, vals :&mut [u8;64]) {
+    ap922_idct_eob_plane(f, eob, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn ap922_idct_eob_plane(f :&[i32;64], eob :usize, plane :&mut [u8], offset :usize, stride :usize) {
+    if eob == 0 {
+        // every sample is dc, stored like store_round
+        let dc = ((f[0] as f32 * AP922_G[0][3]).round() as isize + 128).clamp(0,255) as u8;
+        for y in 0..8 {
+            plane[offset + y * stride..][..8].fill(dc);
+        }
+        return;
+    }
+    let mut ff = [0_f32;64];
+    match eob {
+        1..=2 => ap922_idct_sparse::<2>(f, &mut ff),
+        3..=9 => ap922_idct_sparse::<4>(f, &mut ff),
+        _ => {
+            ff = load_f32(f);
+            ap922_idct_inplace(&mut ff);
+        }
+    }
+    store_round(&ff, plane, offset, stride);
+}
+
+// ap922_idct_inplace for a block with only the top left K x K (2 or 4)
+// coefficients non zero: K columns in the first pass, every row of the
+// second pass has K inputs
+#[inline(always)]
+#[allow(clippy::excessive_precision, clippy::approx_constant, clippy::identity_op, clippy::erasing_op, clippy::unnecessary_cast)]
+fn ap922_idct_sparse<const K :usize>(f :&[i32;64], ff :&mut [f32;64]) {
+    let g4 = 0.707106781186548 as f32;
+    let g = AP922_G;
+    let t = AP922_T;
+
+    for i in 0..K {
+        // row2idx[i] is i for the first 4 columns
+        let g = &g[i];
+        let p0 = f[i] as f32;
+        let p4 = f[1*8+i] as f32;
+        let m = if K == 2 {
+            let tmp0 = p0 * g[3];
+            [tmp0, tmp0, tmp0, tmp0,
+             p4 * g[0], p4 * g[2], p4 * g[4], p4 * g[6]]
+        } else {
+            let p1 = f[2*8+i] as f32;
+            let p5 = f[3*8+i] as f32;
+            let tmp0 = p0 * g[3];
+            let tmp1 = p1 * g[1];
+            let tmp2 = p1 * g[5];
+            [tmp0 + tmp1, tmp0 + tmp2, tmp0 - tmp2, tmp0 - tmp1,
+             p4 * g[0] + p5 * g[2], p4 * g[2] - p5 * g[6], p4 * g[4] - p5 * g[0], p4 * g[6] - p5 * g[4]]
+        };
+        ff[0*8+i] = m[0] + m[4];
+        ff[1*8+i] = m[1] + m[5];
+        ff[2*8+i] = m[2] + m[6];
+        ff[3*8+i] = m[3] + m[7];
+        ff[4*8+i] = m[3] - m[7];
+        ff[5*8+i] = m[2] - m[6];
+        ff[6*8+i] = m[1] - m[5];
+        ff[7*8+i] = m[0] - m[4];
+    }
+
+    for i in 0..8 {
+        let r0 = ff[i*8+0];
+        let r1 = ff[i*8+1];
+        let (e, f5, f6) = if K == 2 {
+            // b = [r0, r0, 0, 0, r1, t2 * r1, 0, 0]
+            let b5 = t[1] * r1;
+            ([r0, r0, r0, r0, r1, r1, b5, b5], g4 * (r1 + b5), g4 * (r1 - b5))
+        } else {
+            let r2 = ff[i*8+2];
+            let r3 = ff[i*8+3];
+            // b = [r0, r0, r2, t1 * r2, r1, t2 * r1, r3, t3 * r3]
+            let b3 = t[0] * r2;
+            let b5 = t[1] * r1;
+            let b7 = t[2] * r3;
+            let e5 = r1 - r3;
+            let e6 = b5 + b7;
+            ([r0 + r2, r0 + b3, r0 - b3, r0 - r2, r1 + r3, e5, e6, b5 - b7], g4 * (e5 + e6), g4 * (e5 - e6))
+        };
+        ff[i*8+0] = e[0] + e[4];
+        ff[i*8+1] = e[1] + f5;
+        ff[i*8+2] = e[2] + f6;
+        ff[i*8+3] = e[3] + e[7];
+        ff[i*8+4] = e[3] - e[7];
+        ff[i*8+5] = e[2] - f6;
+        ff[i*8+6] = e[1] - f5;
+        ff[i*8+7] = e[0] - e[4];
+    }
+}
+
+// runtime dispatch: the best implementation of an algorithm family for
+// this CPU, detected once. The SIMD versions are bit identical to the
+// scalar ones for any input (the islow ones hand blocks past their exact
+// range to islow_idct_into, see x86.rs) so callers just take the function
+// pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Backend {
+    Scalar,
+    Sse2,
+    Avx2,
+}
+
+pub type IdctFn = fn(&[i32;64], &mut [u8;64]);
+
+// 0 is no override, else Backend as u8 + 1
+static BACKEND_OVERRIDE: AtomicU8 = AtomicU8::new(0);
+
+// best backend the CPU supports
+pub fn detected_backend() -> Backend {
+    static DETECTED: OnceLock<Backend> = OnceLock::new();
+    *DETECTED.get_or_init(|| {
+        #[cfg(target_arch = "x86_64")]
+        {
+            if is_x86_feature_detected!("avx2") {
+                return Backend::Avx2;
+            }
+            return Backend::Sse2; // x86_64 baseline
+        }
+        #[allow(unreachable_code)]
+        Backend::Scalar
+    })
+}
+
+// force a backend (e.g. Scalar in tests), None goes back to detection
+// a backend the CPU lacks falls back to the detected one
+pub fn set_backend_override(backend: Option<Backend>) {
+    BACKEND_OVERRIDE.store(backend.map_or(0, |b| b as u8 + 1), Ordering::Relaxed);
+}
+
+// backend the *_fn() functions select from
+pub fn backend() -> Backend {
+    let detected = detected_backend();
+    match BACKEND_OVERRIDE.load(Ordering::Relaxed) {
+        1 => Backend::Scalar,
+        2 => Backend::Sse2.min(detected),
+        3 => Backend::Avx2.min(detected),
+        _ => detected,
+    }
+}
+
+pub fn llm_idct_fn() -> IdctFn {
+    llm_idct_for(backend())
+}
+
+pub fn islow_idct_fn() -> IdctFn {
+    islow_idct_for(backend())
+}
+
+// the dispatch tables, backend must be at most detected_backend()
+fn llm_idct_for(backend: Backend) -> IdctFn {
+    match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => llm_idct_avx2,
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => crate::x86::llm_idct_sse2,
+        _ => llm_idct_into,
+    }
+}
+
+fn islow_idct_for(backend: Backend) -> IdctFn {
+    match backend {
+        #[cfg(target_arch = "x86_64")]
+        Backend::Avx2 => islow_idct_avx2,
+        #[cfg(target_arch = "x86_64")]
+        Backend::Sse2 => crate::x86::islow_idct_sse2,
+        _ => islow_idct_into,
+    }
+}
+
+// no SIMD versions yet, the scalar functions
+pub fn fast_idct_fn() -> IdctFn {
+    fast_idct_into
+}
+
+pub fn ap922_idct_fn() -> IdctFn {
+    ap922_idct_into
+}
+
+// only handed out by backend() when AVX2 was detected
+#[cfg(target_arch = "x86_64")]
+fn llm_idct_avx2(f: &[i32;64], vals: &mut [u8;64]) {
+    unsafe { crate::x86::llm_idct_avx2(f, vals) }
+}
+
+#[cfg(target_arch = "x86_64")]
+fn islow_idct_avx2(f: &[i32;64], vals: &mut [u8;64]) {
+    unsafe { crate::x86::islow_idct_avx2(f, vals) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{ieee1180_corpus, random_corpus, Ieee1180Rand};
+    use crate::zigzag::NATURAL_ORDER;
+
+    // blocks with random coefficients up to zigzag position eob
+    fn sparse_blocks(eob: usize, range: i32) -> Vec<[i32;64]> {
+        let mut rand = Ieee1180Rand::new();
+        (0..500).map(|_| {
+            let mut f = [0;64];
+            for k in 0..=eob {
+                f[NATURAL_ORDER[k]] = rand.next(range, range);
+            }
+            f
+        }).collect()
+    }
+
+    #[test]
+    fn llm_idct_eob_is_bit_identical() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for eob in 0..64 {
+            for range in [5, 300, 2048] {
+                for f in sparse_blocks(eob, range) {
+                    llm_idct_into(&f, &mut want);
+                    llm_idct_eob(&f, eob, &mut got);
+                    assert_eq!(want, got, "eob {} block {:?}", eob, f);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn ap922_idct_eob_is_bit_identical() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for eob in 0..64 {
+            for range in [5, 300, 2048] {
+                for f in sparse_blocks(eob, range) {
+                    ap922_idct_into(&f, &mut want);
+                    ap922_idct_eob(&f, eob, &mut got);
+                    assert_eq!(want, got, "eob {} block {:?}", eob, f);
+                }
+            }
+        }
+    }
+
+    // 1023 * 255, the largest baseline coefficient times the largest table
+    // entry, alone at a few positions and then in every position
+    fn extreme_blocks() -> Vec<[i32;64]> {
+        const MAX: i32 = 1023 * 255;
+        let mut blocks = Vec::new();
+        for pos in [0, 1, 9, 63] {
+            for sign in [1, -1] {
+                let mut f = [0;64];
+                f[pos] = sign * MAX;
+                blocks.push(f);
+            }
+        }
+        for k in 0..8 {
+            blocks.push(core::array::from_fn(|i| if (i * (k + 3) + k) % 3 == 0 {-MAX} else {MAX}));
+        }
+        blocks
+    }
+
+    // libjpeg-turbo jpeg_idct_islow output for extreme_blocks, no overflow
+    #[test]
+    fn islow_idct_extreme_matches_libjpeg() {
+        let want = include_bytes!("../testdata/islow_extreme.raw");
+        let mut got = [0_u8;64];
+        for (f, want) in extreme_blocks().iter().zip(want.chunks_exact(64)) {
+            islow_idct_into(f, &mut got);
+            assert_eq!(&got[..], want, "block {:?}", f);
+        }
+    }
+
+    // libjpeg-turbo jpeg_idct_ifast output for quantized blocks and the
+    // Annex K luma table, 250 blocks each of 1, 3, 10 and 64 coefficients
+    // in -60..=60, then 100 full blocks in -1023..=1023
+    #[test]
+    fn ifast_idct_matches_libjpeg() {
+        let want = include_bytes!("../testdata/ifast_luma.raw");
+        let mut corpus = Vec::new();
+        for nonzero in [1, 3, 10, 64] {
+            corpus.extend(random_corpus(250, 60, nonzero));
+        }
+        corpus.extend(random_corpus(100, 1023, 64));
+        assert_eq!(corpus.len() * 64, want.len());
+        let table = ifast_prepare_quant(&crate::quant::LUMINANCE);
+        let mut got = [0_u8;64];
+        for (f, want) in corpus.iter().zip(want.chunks_exact(64)) {
+            ifast_idct_dequant(f, &table, &mut got);
+            assert_eq!(&got[..], want, "block {:?}", f);
+        }
+    }
+
+    type VecFn = fn(&[i32]) -> Vec<u8>;
+
+    // coefficients of the reduced size fixtures, a quantization table of 1:
+    // 250 blocks each of 1, 3, 10 and 64 coefficients in -300..=300, then
+    // 100 full blocks in -1023..=1023
+    fn reduced_corpus() -> Vec<[i32;64]> {
+        let mut corpus = Vec::new();
+        for nonzero in [1, 3, 10, 64] {
+            corpus.extend(random_corpus(250, 300, nonzero));
+        }
+        corpus.extend(random_corpus(100, 1023, 64));
+        corpus
+    }
+
+    // libjpeg-turbo jpeg_idct_4x4, 2x2 and 1x1 output for reduced_corpus
+    #[test]
+    fn reduced_idct_matches_libjpeg() {
+        let corpus = reduced_corpus();
+        let sizes: [(usize, VecFn, &[u8]); 3] = [
+            (4, idct_4x4, include_bytes!("../testdata/idct_4x4.raw")),
+            (2, idct_2x2, include_bytes!("../testdata/idct_2x2.raw")),
+            (1, idct_1x1, include_bytes!("../testdata/idct_1x1.raw"))];
+        for (n, func, want) in sizes {
+            assert_eq!(corpus.len() * n * n, want.len());
+            for (f, want) in corpus.iter().zip(want.chunks_exact(n * n)) {
+                assert_eq!(func(f), want, "{}x{} block {:?}", n, n, f);
+            }
+        }
+    }
+
+    // libjpeg-turbo jpeg_idct_4x4, 2x2 and 1x1 output for extreme_blocks
+    // as levels of +-1023, plus a block that overflowed 32 bits in both
+    // passes, dequantized by 255 and then by a ramp from 1 to 255
+    #[test]
+    fn reduced_idct_extreme_matches_libjpeg() {
+        let mut levels: Vec<[i32;64]> = extreme_blocks().iter().map(|f| f.map(|v| v / 255)).collect();
+        let mut f = [0;64];
+        f[0] = 1023;
+        f[1] = 1023;
+        f[16] = 1023;
+        f[8] = -1023;
+        levels.push(f);
+        let tables: [[i32;64];2] = [[255;64], core::array::from_fn(|i| 1 + i as i32 * 254 / 63)];
+        let blocks: Vec<[i32;64]> = tables.iter()
+            .flat_map(|q| levels.iter().map(move |f| core::array::from_fn(|i| f[i] * q[i])))
+            .collect();
+        let sizes: [(usize, VecFn, &[u8]); 3] = [
+            (4, idct_4x4, include_bytes!("../testdata/idct_4x4_extreme.raw")),
+            (2, idct_2x2, include_bytes!("../testdata/idct_2x2_extreme.raw")),
+            (1, idct_1x1, include_bytes!("../testdata/idct_1x1_extreme.raw"))];
+        for (n, func, want) in sizes {
+            assert_eq!(blocks.len() * n * n, want.len());
+            for (f, want) in blocks.iter().zip(want.chunks_exact(n * n)) {
+                assert_eq!(func(f), want, "{}x{} block {:?}", n, n, f);
+            }
+        }
+    }
+
+    // a DC only block is flat at the DC level in every size
+    #[test]
+    fn reduced_idct_dc_only() {
+        for dc in [-1024, -800, -8, 0, 7, 8, 400, 1016] {
+            let mut f = [0;64];
+            f[0] = dc;
+            let level = (128 + (dc as f64 / 8.0).round() as i32).clamp(0, 255) as u8;
+            let mut v4 = [0_u8;16];
+            let mut v2 = [0_u8;4];
+            let mut v1 = [0_u8;1];
+            idct_4x4_into(&f, &mut v4);
+            idct_2x2_into(&f, &mut v2);
+            idct_1x1_into(&f, &mut v1);
+            assert!(v4.iter().chain(&v2).chain(&v1).all(|v| *v == level), "dc {}: {:?} {:?} {:?}", dc, v4, v2, v1);
+        }
+    }
+
+    // _into and _plane give the Vec output, _plane writes only the n x n block
+    #[test]
+    fn reduced_idct_plane() {
+        const OFFSET: usize = 3;
+        const STRIDE: usize = 7;
+        let planes: [(usize, VecFn, crate::plane::PlaneFn); 3] = [
+            (4, idct_4x4, idct_4x4_plane), (2, idct_2x2, idct_2x2_plane), (1, idct_1x1, idct_1x1_plane)];
+        let mut v4 = [0_u8;16];
+        let mut v2 = [0_u8;4];
+        let mut v1 = [0_u8;1];
+        for f in reduced_corpus().iter().step_by(5) {
+            idct_4x4_into(f, &mut v4);
+            idct_2x2_into(f, &mut v2);
+            idct_1x1_into(f, &mut v1);
+            assert_eq!((idct_4x4(f), idct_2x2(f), idct_1x1(f)), (v4.to_vec(), v2.to_vec(), v1.to_vec()));
+
+            for (n, vec, plane) in planes {
+                let want = vec(f);
+                let mut buf = vec![0xa5_u8; OFFSET + n * STRIDE + 2];
+                plane(f, &mut buf, OFFSET, STRIDE);
+                for (i, val) in buf.iter().enumerate() {
+                    let (y, x) = ((i as isize - OFFSET as isize).div_euclid(STRIDE as isize),
+                        (i as isize - OFFSET as isize).rem_euclid(STRIDE as isize) as usize);
+                    if (0..n as isize).contains(&y) && x < n {
+                        assert_eq!(*val, want[y as usize * n + x], "{}x{} plane, block {:?}", n, n, f);
+                    } else {
+                        assert_eq!(*val, 0xa5, "{}x{} plane wrote byte {} outside the block", n, n, i);
+                    }
+                }
+            }
+        }
+    }
+
+    // the Vec, _into, _inplace and _plane entry points of one algorithm
+    // give the same samples, and _plane writes nothing outside the block
+    fn check_entry_points(name: &str, vec: fn(&[i32]) -> Vec<u8>, into: fn(&[i32;64], &mut [u8;64]),
+        inplace: fn(&mut [f32;64]), plane: fn(&[i32;64], &mut [u8], usize, usize),
+        store: fn(&[f32;64], &mut [u8], usize, usize)) {
+        const OFFSET: usize = 5;
+        const STRIDE: usize = 13;
+        let mut corpus = ieee1180_corpus(500, 256, 255, 1);
+        corpus.extend(random_corpus(500, 1000, 10));
+        let mut got = [0_u8;64];
+        for f in &corpus {
+            let want = vec(f);
+            into(f, &mut got);
+            assert_eq!(want, got, "{} into, block {:?}", name, f);
+
+            let mut ff = load_f32(f);
+            inplace(&mut ff);
+            store(&ff, &mut got, 0, 8);
+            assert_eq!(want, got, "{} inplace, block {:?}", name, f);
+
+            let mut buf = vec![0xa5_u8; OFFSET + 8 * STRIDE + 3];
+            plane(f, &mut buf, OFFSET, STRIDE);
+            for (i, val) in buf.iter().enumerate() {
+                let (y, x) = ((i as isize - OFFSET as isize).div_euclid(STRIDE as isize),
+                    (i as isize - OFFSET as isize).rem_euclid(STRIDE as isize));
+                if (0..8).contains(&y) && x < 8 {
+                    assert_eq!(*val, want[y as usize * 8 + x as usize], "{} plane, block {:?}", name, f);
+                } else {
+                    assert_eq!(*val, 0xa5, "{} plane wrote byte {} outside the block", name, i);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn entry_points_match_vec_api() {
+        check_entry_points("llm", llm_idct, llm_idct_into, llm_idct_inplace, llm_idct_plane, store_truncate);
+        check_entry_points("fast", fast_idct, fast_idct_into, fast_idct_inplace, fast_idct_plane, store_truncate);
+        check_entry_points("ap922", ap922_idct, ap922_idct_into, ap922_idct_inplace, ap922_idct_plane, store_round);
+    }
+
+    // quantized blocks and tables: the folded dequantize against f * q
+    // through the plain IDCT
+    fn dequant_corpus() -> Vec<([i32;64], [u16;64])> {
+        let mut rand = Ieee1180Rand::new();
+        let tables = [crate::quant::LUMINANCE, crate::quant::CHROMINANCE, [1;64],
+            core::array::from_fn(|_| rand.next(0, 254) as u16 + 1)];
+        let mut corpus = Vec::new();
+        for (i, f) in random_corpus(4000, 30, 10).into_iter().enumerate() {
+            corpus.push((f, tables[i % tables.len()]));
+        }
+        corpus
+    }
+
+    #[test]
+    fn llm_idct_dequant_is_bit_identical() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for (f, q) in dequant_corpus() {
+            let table = llm_prepare_quant(&q);
+            // the table is q / 8, a power of 2 away from q: no rounding moves
+            llm_idct_into(&core::array::from_fn(|i| f[i] * q[i] as i32), &mut want);
+            llm_idct_dequant(&f, &table, &mut got);
+            assert_eq!(want, got, "block {:?} table {:?}", f, q);
+        }
+    }
+
+    #[test]
+    fn fast_idct_dequant_matches_dequantize() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        let (mut off, mut total) = (0, 0);
+        for (f, q) in dequant_corpus() {
+            let table = fast_prepare_quant(&q);
+            fast_idct_into(&core::array::from_fn(|i| f[i] * q[i] as i32), &mut want);
+            fast_idct_dequant(&f, &table, &mut got);
+            for (a, b) in want.iter().zip(&got) {
+                // q * s[u] * s[v] rounds once, the two prescales twice
+                assert!((*a as i32 - *b as i32).abs() <= 1, "block {:?} table {:?}", f, q);
+                off += (a != b) as usize;
+                total += 1;
+            }
+        }
+        assert!(off * 10000 < total, "{} of {} samples off by one", off, total);
+    }
+
+    // blocks with many all zero AC rows and columns, and some without
+    fn pruning_corpus() -> Vec<[i32;64]> {
+        let mut corpus = Vec::new();
+        for nonzero in 1..=64 {
+            corpus.extend(random_corpus(100, 300, nonzero));
+        }
+        let mut rand = Ieee1180Rand::new();
+        for mut f in random_corpus(2000, 1000, 64) {
+            // clear the AC terms of a random set of rows and columns
+            let rows = rand.next(0, 255);
+            let cols = rand.next(0, 255);
+            for (i, val) in f.iter_mut().enumerate() {
+                if (rows >> (i / 8)) & 1 == 1 && i % 8 != 0
+                    || (cols >> (i % 8)) & 1 == 1 && i / 8 != 0 {
+                    *val = 0;
+                }
+            }
+            corpus.push(f);
+        }
+        corpus.extend(ieee1180_corpus(1000, 5, 5, 1));
+        corpus
+    }
+
+    fn assert_same_bits(pruned: &[f32], full: &[f32], f: &[i32;64]) {
+        let same = pruned.iter().zip(full).all(|(a, b)| a.to_bits() == b.to_bits() || *a == 0.0 && *b == 0.0);
+        assert!(same, "pruned {:?} full {:?} block {:?}", pruned, full, f);
+    }
+
+    #[test]
+    fn llm_idct_pruning_is_bit_identical() {
+        for f in pruning_corpus() {
+            let mut pruned = load_f32(&f);
+            let mut full = load_f32(&f);
+            llm_idct_kernel::<true>(&mut pruned, 0.125);
+            llm_idct_kernel::<false>(&mut full, 0.125);
+            assert_same_bits(&pruned, &full, &f);
+        }
+    }
+
+    #[test]
+    fn fast_idct_pruning_is_bit_identical() {
+        for f in pruning_corpus() {
+            let mut pruned = load_f32(&f);
+            let mut full = load_f32(&f);
+            fast_idct_kernel::<true, true>(&mut pruned);
+            fast_idct_kernel::<false, true>(&mut full);
+            assert_same_bits(&pruned, &full, &f);
+        }
+    }
+
+    #[test]
+    fn fast_idct_f64_pruning_is_bit_identical() {
+        for f in pruning_corpus() {
+            let mut pruned = [0_f64;64];
+            for i in 0..64 {
+                pruned[i] = f[i] as f64;
+            }
+            let mut full = pruned;
+            fast_idct_f64_kernel::<true>(&mut pruned);
+            fast_idct_f64_kernel::<false>(&mut full);
+            let same = pruned.iter().zip(&full).all(|(a, b)| a.to_bits() == b.to_bits() || *a == 0.0 && *b == 0.0);
+            assert!(same, "pruned {:?} full {:?} block {:?}", pruned, full, f);
+        }
+    }
+
+    #[test]
+    fn ap922_idct_pruning_is_bit_identical() {
+        for f in pruning_corpus() {
+            let mut pruned = load_f32(&f);
+            let mut full = load_f32(&f);
+            ap922_idct_kernel::<true>(&mut pruned);
+            ap922_idct_kernel::<false>(&mut full);
+            assert_same_bits(&pruned, &full, &f);
+        }
+    }
+
+    // the kernels behind each backend the CPU has, called directly: the
+    // override is global and other tests dispatch in parallel
+    #[test]
+    fn dispatch_matches_scalar() {
+        let mut corpus = ieee1180_corpus(2000, 256, 255, 1);
+        corpus.extend(random_corpus(2000, 1000, 64));
+        corpus.extend(random_corpus(2000, 2047, 64));
+        corpus.extend(extreme_blocks());
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for b in [Backend::Scalar, Backend::Sse2, Backend::Avx2] {
+            if b > detected_backend() {
+                continue;
+            }
+            for f in &corpus {
+                llm_idct_into(f, &mut want);
+                llm_idct_for(b)(f, &mut got);
+                assert_eq!(want, got, "llm {:?}", b);
+                islow_idct_into(f, &mut want);
+                islow_idct_for(b)(f, &mut got);
+                assert_eq!(want, got, "islow {:?}", b);
+            }
+        }
+        assert!(backend() <= detected_backend());
+    }
+
+    // the whole override in one test so no other test changes it
+    // meanwhile; tests dispatching in parallel see bit identical kernels
+    #[test]
+    fn backend_override() {
+        let previous = BACKEND_OVERRIDE.load(Ordering::Relaxed);
+
+        set_backend_override(Some(Backend::Scalar));
+        assert_eq!(backend(), Backend::Scalar);
+        assert_eq!(llm_idct_fn() as usize, llm_idct_into as IdctFn as usize);
+        assert_eq!(islow_idct_fn() as usize, islow_idct_into as IdctFn as usize);
+
+        // above the CPU's backend clamps to it
+        for b in [Backend::Sse2, Backend::Avx2] {
+            set_backend_override(Some(b));
+            assert_eq!(backend(), b.min(detected_backend()), "override {:?}", b);
+            assert_eq!(llm_idct_fn() as usize, llm_idct_for(backend()) as usize, "override {:?}", b);
+        }
+
+        set_backend_override(None);
+        assert_eq!(backend(), detected_backend());
+
+        BACKEND_OVERRIDE.store(previous, Ordering::Relaxed);
+    }
+}
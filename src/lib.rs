@@ -1,2 +1,22 @@
+#![cfg_attr(feature = "portable_simd", feature(portable_simd))]
+
 pub mod idct;
-pub mod fdct;
\ No newline at end of file
+pub mod fdct;
+pub mod transform;
+pub mod scaled;
+pub mod batch;
+pub mod plane;
+pub mod zigzag;
+pub mod quant;
+pub mod huffman;
+pub mod trellis;
+pub mod decoder;
+pub mod encoder;
+#[cfg(target_arch = "x86_64")]
+pub mod x86;
+#[cfg(feature = "portable_simd")]
+pub mod portable;
+pub mod accuracy;
+
+#[cfg(test)]
+mod ieee1180;
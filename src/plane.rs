@@ -0,0 +1,100 @@
+// IDCT of a whole component plane
+//
+// f holds width x height blocks in raster order, block (bx, by) at
+// f[(by * width + bx) * 64..][..64]. Block (bx, by) is written to the 8x8
+// samples at plane[(by * 8 + y) * stride + bx * 8 + x], so stride >= width * 8
+// and the plane needs (height * 8 - 1) * stride + width * 8 bytes.
+// func is any 8x8 `*_plane` function of idct.rs (or InverseDct::idct_plane).
+//
+// par_idct_plane (`--features parallel`) splits the plane by block rows over
+// the rayon thread pool, every row is one task writing its own 8 lines.
+
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+
+pub type PlaneFn = fn(&[i32;64], &mut [u8], usize, usize);
+
+fn check_plane(f: &[i32], width: usize, height: usize, plane: &[u8], stride: usize) {
+    assert_eq!(f.len(), width * height * 64, "coefficients are not {} x {} blocks", width, height);
+    assert!(stride >= width * 8, "stride {} is less than {} samples", stride, width * 8);
+    if height > 0 {
+        assert!(plane.len() >= (height * 8 - 1) * stride + width * 8, "plane too small");
+    }
+}
+
+// one row of blocks, rows are the 8 lines of the block row
+fn block_row(f: &[i32], rows: &mut [u8], stride: usize, func: PlaneFn) {
+    for (bx, block) in f.chunks_exact(64).enumerate() {
+        func(block.try_into().unwrap(), rows, bx * 8, stride);
+    }
+}
+
+pub fn idct_plane(f: &[i32], width: usize, height: usize, plane: &mut [u8], stride: usize, func: PlaneFn) {
+    check_plane(f, width, height, plane, stride);
+    if width == 0 {
+        return;
+    }
+    for (row, rows) in f.chunks_exact(width * 64).zip(plane.chunks_mut(8 * stride)) {
+        block_row(row, rows, stride, func);
+    }
+}
+
+// same output as idct_plane
+#[cfg(feature = "parallel")]
+pub fn par_idct_plane(f: &[i32], width: usize, height: usize, plane: &mut [u8], stride: usize, func: PlaneFn) {
+    check_plane(f, width, height, plane, stride);
+    if width == 0 {
+        return;
+    }
+    f.par_chunks_exact(width * 64)
+        .zip(plane.par_chunks_mut(8 * stride))
+        .for_each(|(row, rows)| block_row(row, rows, stride, func));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::random_corpus;
+    use crate::idct;
+
+    const W: usize = 7;
+    const H: usize = 5;
+    const STRIDE: usize = W * 8 + 3;
+
+    fn coefficients() -> Vec<i32> {
+        random_corpus(W * H, 500, 10).into_iter().flatten().collect()
+    }
+
+    #[test]
+    fn blocks_land_in_raster_order() {
+        let f = coefficients();
+        // the plane ends at the last sample, no padding after the last line
+        let mut plane = vec![0_u8; (H * 8 - 1) * STRIDE + W * 8];
+        idct_plane(&f, W, H, &mut plane, STRIDE, idct::llm_idct_plane);
+
+        let mut want = [0_u8;64];
+        for (i, block) in f.chunks_exact(64).enumerate() {
+            let (bx, by) = (i % W, i / W);
+            idct::llm_idct_into(block.try_into().unwrap(), &mut want);
+            for y in 0..8 {
+                let start = (by * 8 + y) * STRIDE + bx * 8;
+                assert_eq!(&plane[start..start + 8], &want[y * 8..y * 8 + 8], "block {}, {}", bx, by);
+            }
+        }
+        // padding columns untouched
+        for line in plane.chunks(STRIDE) {
+            assert!(line[W * 8..].iter().all(|v| *v == 0));
+        }
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn parallel_matches_sequential() {
+        let f = coefficients();
+        let mut want = vec![0_u8; H * 8 * STRIDE];
+        let mut got = vec![0_u8; H * 8 * STRIDE];
+        idct_plane(&f, W, H, &mut want, STRIDE, idct::islow_idct_plane);
+        par_idct_plane(&f, W, H, &mut got, STRIDE, idct::islow_idct_plane);
+        assert_eq!(want, got);
+    }
+}
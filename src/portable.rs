@@ -0,0 +1,198 @@
+#![allow(clippy::excessive_precision, clippy::approx_constant)]
+// std::simd versions of fast_idct, llm_idct and llm_fdct (nightly,
+// `--features portable_simd`)
+//
+// f32x8 holds one coefficient (or sample) index of all 8 rows or columns,
+// the IDCT passes are llm_1d / aan_1d of idct.rs and the FDCT pass is the
+// one of fdct::llm_fdct, with a transpose where the scalar pass runs along
+// a row. Same expressions in the same order and no FMA, so the output is
+// bit identical to the scalar functions on every target.
+
+use std::simd::num::{SimdFloat, SimdInt, SimdUint};
+use std::simd::{f32x8, i32x8, u8x8};
+
+use crate::idct::{aan_1d, llm_1d, Lanes};
+
+impl Lanes for f32x8 {
+    #[inline(always)]
+    fn splat(x: f32) -> Self {
+        f32x8::splat(x)
+    }
+}
+
+// vectors r[k] become vectors of element k of every r: three rounds of
+// interleaving r[i] with r[i + 4] is a perfect shuffle of the 6 index bits
+#[inline(always)]
+fn transpose(r: &[f32x8;8]) -> [f32x8;8] {
+    let mut r = *r;
+    for _ in 0..3 {
+        let mut t = r;
+        for i in 0..4 {
+            (t[2 * i], t[2 * i + 1]) = r[i].interleave(r[i + 4]);
+        }
+        r = t;
+    }
+    r
+}
+
+#[inline(always)]
+fn load(f: &[i32;64]) -> [f32x8;8] {
+    core::array::from_fn(|y| i32x8::from_slice(&f[y * 8..]).cast::<f32>())
+}
+
+// store_truncate: level shift, clamp, truncate. Clamping first gives the
+// same bytes as `as i32` then clamp, max and min also turn NaN into 0
+#[inline(always)]
+fn store_truncate(rows: &[f32x8;8], vals: &mut [u8;64]) {
+    let lo = f32x8::splat(0.0);
+    let hi = f32x8::splat(255.0);
+    for (y, row) in rows.iter().enumerate() {
+        let v = (*row + f32x8::splat(128.0)).simd_max(lo).simd_min(hi);
+        // SAFETY: v is in 0.0..=255.0
+        let v: u8x8 = unsafe { v.to_int_unchecked() };
+        v.copy_to_slice(&mut vals[y * 8..y * 8 + 8]);
+    }
+}
+
+pub fn llm_idct(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    llm_idct_into(f[..64].try_into().unwrap(), &mut vals);
+    vals.to_vec()
+}
+
+// bit identical to idct::llm_idct_into
+pub fn llm_idct_into(f: &[i32;64], vals: &mut [u8;64]) {
+    // pass 1 runs along the rows: the rows in the lanes
+    let t = llm_1d(transpose(&load(f)));
+    // pass 2 along the columns: the columns in the lanes
+    let m0 = f32x8::splat(0.125);
+    let o = llm_1d(transpose(&t)).map(|v| v * m0);
+    store_truncate(&o, vals);
+}
+
+pub fn fast_idct(f: &[i32]) -> Vec<u8> {
+    let mut vals = [0_u8;64];
+    fast_idct_into(f[..64].try_into().unwrap(), &mut vals);
+    vals.to_vec()
+}
+
+// bit identical to idct::fast_idct_into
+pub fn fast_idct_into(f: &[i32;64], vals: &mut [u8;64]) {
+    // pass 1 runs along the columns: the rows are the vectors already
+    let t = aan_1d::<_, true>(load(f));
+    // pass 2 along the rows, transposed back for the store
+    let o = transpose(&aan_1d::<_, true>(transpose(&t)));
+    store_truncate(&o, vals);
+}
+
+// one LL&M FDCT 1D pass, as fdct::llm_fdct
+#[inline(always)]
+fn llm_fdct_1d(f: &[f32x8;8]) -> [f32x8;8] {
+    let m0 = f32x8::splat(0.7071067811865475);
+    let m1 = f32x8::splat(1.3870398453221475);
+    let m2 = f32x8::splat(1.3065629648763766);
+    let m3 = f32x8::splat(1.1758756024193588);
+    let m5 = f32x8::splat(0.7856949583871023);
+    let m6 = f32x8::splat(0.5411961001461971);
+    let m7 = f32x8::splat(0.2758993792829431);
+    let [f0, f1, f2, f3, f4, f5, f6, f7] = *f;
+
+    let a0 = f0 + f7;
+    let a7 = f0 - f7;
+    let a1 = f1 + f6;
+    let a6 = f1 - f6;
+    let a2 = f2 + f5;
+    let a5 = f2 - f5;
+    let a3 = f3 + f4;
+    let a4 = f3 - f4;
+
+    let c0 = a0 + a3;
+    let c3 = a0 - a3;
+    let c1 = a1 + a2;
+    let c2 = a1 - a2;
+
+    let z0 = c0 + c1;
+    let z4 = c0 - c1;
+    let z2 = c2 * m6 + c3 * m2;
+    let z6 = c3 * m6 - c2 * m2;
+
+    let c3 = a4 * m3 + a7 * m5;
+    let c0 = a7 * m3 - a4 * m5;
+    let c2 = a5 * m1 + a6 * m7;
+    let c1 = a6 * m1 - a5 * m7;
+
+    let z5 = c3 - c1;
+    let z3 = c0 - c2;
+
+    let d0 = (c0 + c2) * m0;
+    let d3 = (c3 + c1) * m0;
+
+    [z0, d0 + d3, z2, z3, z4, z5, z6, d0 - d3]
+}
+
+// bit identical to fdct::llm_fdct
+pub fn llm_fdct(f: &[u8]) -> Vec<f32> {
+    let c128 = f32x8::splat(128.0);
+    let rows: [f32x8;8] = core::array::from_fn(|y| {
+        u8x8::from_slice(&f[y * 8..]).cast::<f32>() - c128
+    });
+    // pass 1 along the rows, pass 2 along the columns
+    let t = llm_fdct_1d(&transpose(&rows));
+    let m = f32x8::splat(0.125);
+    let o = llm_fdct_1d(&transpose(&t)).map(|v| v * m);
+    let mut zz = vec![0_f32;64];
+    for (y, row) in o.iter().enumerate() {
+        row.copy_to_slice(&mut zz[y * 8..y * 8 + 8]);
+    }
+    zz
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{ieee1180_corpus, random_corpus, Ieee1180Rand};
+    use crate::{fdct, idct};
+
+    fn corpus() -> Vec<[i32;64]> {
+        let mut corpus = ieee1180_corpus(2000, 256, 255, 1);
+        corpus.extend(ieee1180_corpus(2000, 5, 5, -1));
+        for nonzero in [1, 3, 10, 64] {
+            corpus.extend(random_corpus(1000, 1000, nonzero));
+        }
+        corpus
+    }
+
+    #[test]
+    fn llm_idct_matches_scalar() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in corpus() {
+            idct::llm_idct_into(&f, &mut want);
+            llm_idct_into(&f, &mut got);
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+
+    #[test]
+    fn fast_idct_matches_scalar() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in corpus() {
+            idct::fast_idct_into(&f, &mut want);
+            fast_idct_into(&f, &mut got);
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+
+    #[test]
+    fn llm_fdct_matches_scalar() {
+        let mut rand = Ieee1180Rand::new();
+        for _ in 0..5000 {
+            let f: Vec<u8> = (0..64).map(|_| rand.next(0, 255) as u8).collect();
+            let want = fdct::llm_fdct(&f);
+            let got = llm_fdct(&f);
+            let same = want.iter().zip(&got).all(|(a, b)| a.to_bits() == b.to_bits() || *a == 0.0 && *b == 0.0);
+            assert!(same, "samples {:?}", f);
+        }
+    }
+}
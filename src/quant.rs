@@ -0,0 +1,129 @@
+// Quantization tables and quantization of transform output
+//
+// Tables are in natural order q[v*8 + u], like the coefficients of idct.rs
+// and fdct.rs (zigzag::to_zigzag gives the DQT order). Quality scaling is
+// the IJG one (jcparam.c jpeg_set_quality), so quality n gives the same
+// tables as cjpeg -quality n.
+
+use crate::transform::{ForwardDct, InverseDct};
+
+// T.81 Annex K Table K.1
+pub const LUMINANCE: [u16;64] = [
+    16, 11, 10, 16, 24, 40, 51, 61,
+    12, 12, 14, 19, 26, 58, 60, 55,
+    14, 13, 16, 24, 40, 57, 69, 56,
+    14, 17, 22, 29, 51, 87, 80, 62,
+    18, 22, 37, 56, 68,109,103, 77,
+    24, 35, 55, 64, 81,104,113, 92,
+    49, 64, 78, 87,103,121,120,101,
+    72, 92, 95, 98,112,100,103, 99];
+
+// T.81 Annex K Table K.2
+pub const CHROMINANCE: [u16;64] = [
+    17, 18, 24, 47, 99, 99, 99, 99,
+    18, 21, 26, 66, 99, 99, 99, 99,
+    24, 26, 56, 99, 99, 99, 99, 99,
+    47, 66, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99,
+    99, 99, 99, 99, 99, 99, 99, 99];
+
+// jpeg_quality_scaling: quality 1..100 (clamped) to a percentage of the
+// Annex K tables, 50 is the tables as given, 100 is all 1
+pub fn quality_scaling(quality: u32) -> u32 {
+    let quality = quality.clamp(1, 100);
+    if quality < 50 {
+        5000 / quality
+    } else {
+        200 - quality * 2
+    }
+}
+
+// jpeg_add_quant_table: base * scale / 100 rounded, at least 1; limited to
+// 255 for baseline (8 bit DQT entries), to 32767 otherwise
+pub fn scale_table(base: &[u16;64], scale: u32, baseline: bool) -> [u16;64] {
+    let max = if baseline { 255 } else { 32767 };
+    base.map(|q| ((q as u32 * scale + 50) / 100).clamp(1, max) as u16)
+}
+
+// base scaled for quality 1..100, baseline tables
+pub fn quality_table(base: &[u16;64], quality: u32) -> [u16;64] {
+    scale_table(base, quality_scaling(quality), true)
+}
+
+// f is 64 normalized coefficients (fdct output), rounded to nearest
+pub fn quantize(f: &[f32], q: &[u16;64]) -> [i32;64] {
+    core::array::from_fn(|i| (f[i] / q[i] as f32).round() as i32)
+}
+
+pub fn dequantize(f: &[i32;64], q: &[u16;64]) -> [i32;64] {
+    core::array::from_fn(|i| f[i] * q[i] as i32)
+}
+
+// samples to quantized coefficients with any ForwardDct
+pub fn fdct_quantize<T: ForwardDct>(f: &[u8], q: &[u16;64]) -> [i32;64] {
+    quantize(&T::fdct(f), q)
+}
+
+// quantized coefficients to samples with any InverseDct
+pub fn dequantize_idct<T: InverseDct>(f: &[i32;64], q: &[u16;64], out: &mut [u8;64]) {
+    T::idct_into(&dequantize(f, q), out);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::Ieee1180Rand;
+    use crate::transform::{IslowIdct, LlmFdct, LlmIdct, StandardFdct};
+
+    #[test]
+    fn ijg_quality_tables() {
+        assert_eq!(quality_table(&LUMINANCE, 50), LUMINANCE);
+        assert_eq!(quality_table(&CHROMINANCE, 50), CHROMINANCE);
+        assert_eq!(quality_table(&LUMINANCE, 100), [1;64]);
+        // cjpeg -quality 75 and 10
+        assert_eq!(quality_table(&LUMINANCE, 75)[..8], [8, 6, 5, 8, 12, 20, 26, 31]);
+        assert_eq!(quality_table(&CHROMINANCE, 75)[..4], [9, 9, 12, 24]);
+        assert_eq!(quality_table(&LUMINANCE, 10)[..4], [80, 55, 50, 80]);
+        // out of range qualities clamp, baseline caps at 255
+        assert_eq!(quality_table(&LUMINANCE, 0), quality_table(&LUMINANCE, 1));
+        assert_eq!(quality_table(&LUMINANCE, 1)[0], 255);
+        assert_eq!(scale_table(&LUMINANCE, quality_scaling(1), false)[0], 800);
+        assert_eq!(scale_table(&CHROMINANCE, 100_000, false)[63], 32767);
+    }
+
+    #[test]
+    fn quantize_rounds_to_nearest() {
+        let mut f = [0_f32;64];
+        f[..6].copy_from_slice(&[15.9, -15.9, 8.0, -8.0, 7.9, -24.1]);
+        let q = quantize(&f, &[16;64]);
+        assert_eq!(q[..6], [1, -1, 1, -1, 0, -2]);
+        assert_eq!(dequantize(&q, &[16;64])[..6], [16, -16, 16, -16, 0, -32]);
+    }
+
+    // a smooth block survives quality 90 within a few levels
+    #[test]
+    fn transform_round_trip() {
+        let mut rand = Ieee1180Rand::new();
+        let q = quality_table(&LUMINANCE, 90);
+        let mut out = [0_u8;64];
+        for _ in 0..500 {
+            let (a, b) = (rand.next(4, 4), rand.next(4, 4));
+            let base = rand.next(-60, 190);
+            let f: Vec<u8> = (0..64).map(|i| (base + a * (i % 8) + b * (i / 8)) as u8).collect();
+            let coefficients = fdct_quantize::<LlmFdct>(&f, &q);
+            assert_eq!(coefficients, fdct_quantize::<StandardFdct>(&f, &q), "samples {:?}", f);
+            dequantize_idct::<LlmIdct>(&coefficients, &q, &mut out);
+            let mut err = 0;
+            for (a, b) in f.iter().zip(&out) {
+                err = err.max((*a as i32 - *b as i32).abs());
+            }
+            assert!(err <= 4, "error {}, samples {:?}", err, f);
+
+            let mut islow = [0_u8;64];
+            dequantize_idct::<IslowIdct>(&coefficients, &q, &mut islow);
+            assert!(out.iter().zip(&islow).all(|(a, b)| (*a as i32 - *b as i32).abs() <= 1));
+        }
+    }
+}
@@ -0,0 +1,189 @@
+// NxN scaled IDCT, N = 1..16, for libjpeg style scale_num / scale_denom
+//
+// The 8x8 IDCT samples g(t) = Σ C(u)/2 F(u) cos((2t + 1)uπ / 16) at
+// t = 0..7. An N sample output covering the same block samples g at the
+// centers of N equal cells, (2t + 1) = (2x + 1) * 8 / N, so the basis is
+// cos((2x + 1)uπ / 2N) with the normalization of the 8 point IDCT: the DC
+// level is unchanged and a 4x4 output is close to the 2x2 mean of the 8x8.
+// Like libjpeg's jpeg_idct_NxN only u < N is used when scaling down,
+// the higher frequencies would alias.
+
+use core::f32::consts::PI;
+
+#[derive(Debug, Clone)]
+pub struct ScaledIdct {
+    n: usize,
+    terms: usize,          // min(n, 8) coefficients per direction
+    basis: [[f32;8];16],   // basis[x][u] = C(u)/2 * cos((2x + 1)uπ / 2N)
+}
+
+impl ScaledIdct {
+    // n x n output per 8x8 block, panics unless 1 <= n <= 16
+    pub fn new(n: usize) -> Self {
+        assert!((1..=16).contains(&n), "scaled IDCT size must be 1..16, got {}", n);
+        let mut basis = [[0.0;8];16];
+        for (x, row) in basis.iter_mut().take(n).enumerate() {
+            for (u, val) in row.iter_mut().enumerate() {
+                let cu = if u == 0 {1.0 / 2.0_f32.sqrt()} else {1.0};
+                *val = cu / 2.0 * ((2 * x + 1) as f32 * u as f32 * PI / (2 * n) as f32).cos();
+            }
+        }
+        ScaledIdct { n, terms: n.min(8), basis }
+    }
+
+    // output size like jpeg_core_output_dimensions: the smallest N with
+    // num / denom <= N / 8, None when that is 0 or over 16
+    pub fn from_ratio(num: usize, denom: usize) -> Option<Self> {
+        if num == 0 || denom == 0 {
+            return None;
+        }
+        let n = (num * 8).div_ceil(denom);
+        if n > 16 {
+            return None;
+        }
+        Some(Self::new(n))
+    }
+
+    pub fn size(&self) -> usize {
+        self.n
+    }
+
+    pub fn idct(&self, f: &[i32]) -> Vec<u8> {
+        let mut vals = vec![0_u8; self.n * self.n];
+        self.idct_plane(f[..64].try_into().unwrap(), &mut vals, 0, self.n);
+        vals
+    }
+
+    // vals is n * n level shifted samples
+    pub fn idct_into(&self, f: &[i32;64], vals: &mut [u8]) {
+        self.idct_plane(f, vals, 0, self.n);
+    }
+
+    // row y of the block is written to plane[offset + y * stride..][..n]
+    pub fn idct_plane(&self, f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+        let n = self.n;
+        let terms = self.terms;
+
+        // rows: ws[v][x] = Σu basis[x][u] * F(v, u)
+        let mut ws = [[0.0_f32;16];8];
+        for v in 0..terms {
+            let row = &f[v * 8..v * 8 + 8];
+            if row[..terms].iter().all(|c| *c == 0) {
+                continue;
+            }
+            for (x, val) in ws[v].iter_mut().take(n).enumerate() {
+                *val = self.basis[x][..terms].iter().zip(row).map(|(b, c)| b * *c as f32).sum();
+            }
+        }
+
+        // columns: out[y][x] = Σv basis[y][v] * ws[v][x]
+        for y in 0..n {
+            let out = &mut plane[offset + y * stride..][..n];
+            for (x, val_out) in out.iter_mut().enumerate() {
+                let val: f32 = self.basis[y][..terms].iter().zip(&ws).map(|(b, w)| b * w[x]).sum();
+                *val_out = (val.round() as i32 + 128).clamp(0, 255) as u8;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::random_corpus;
+    use crate::idct;
+
+    #[test]
+    fn from_ratio() {
+        assert_eq!(ScaledIdct::from_ratio(1, 1).unwrap().size(), 8);
+        assert_eq!(ScaledIdct::from_ratio(3, 8).unwrap().size(), 3);
+        assert_eq!(ScaledIdct::from_ratio(13, 8).unwrap().size(), 13);
+        assert_eq!(ScaledIdct::from_ratio(1, 3).unwrap().size(), 3);
+        assert_eq!(ScaledIdct::from_ratio(2, 1).unwrap().size(), 16);
+        assert!(ScaledIdct::from_ratio(3, 1).is_none());
+        assert!(ScaledIdct::from_ratio(0, 8).is_none());
+    }
+
+    #[test]
+    fn dc_level_is_kept() {
+        let mut f = [0;64];
+        f[0] = 400; // level 50
+        for n in 1..=16 {
+            let out = ScaledIdct::new(n).idct(&f);
+            assert_eq!(out.len(), n * n);
+            assert!(out.iter().all(|v| *v == 178), "n = {}", n);
+        }
+    }
+
+    #[test]
+    fn size_8_is_the_8x8_idct() {
+        let scaled = ScaledIdct::new(8);
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in random_corpus(1000, 300, 64) {
+            idct::idct_f64_into(&f, &mut want);
+            scaled.idct_into(&f, &mut got);
+            for i in 0..64 {
+                assert!((want[i] as i32 - got[i] as i32).abs() <= 1);
+            }
+        }
+    }
+
+    // libjpeg's 4x4 and 2x2 average the 8x8 over each cell and fold in
+    // the frequencies above n, so they agree only on small blocks with
+    // nothing outside the top left n x n
+    #[test]
+    fn small_sizes_are_the_reduced_idcts() {
+        type VecFn = fn(&[i32]) -> Vec<u8>;
+        let sizes: [(usize, VecFn); 3] = [(4, idct::idct_4x4), (2, idct::idct_2x2), (1, idct::idct_1x1)];
+        for (n, reduced) in sizes {
+            let scaled = ScaledIdct::new(n);
+            for mut f in random_corpus(1000, 5, 64) {
+                for (i, val) in f.iter_mut().enumerate() {
+                    if i / 8 >= n || i % 8 >= n {
+                        *val = 0;
+                    }
+                }
+                let (want, got) = (reduced(&f), scaled.idct(&f));
+                for i in 0..n * n {
+                    assert!((want[i] as i32 - got[i] as i32).abs() <= 1, "{}x{} block {:?}", n, n, f);
+                }
+            }
+        }
+    }
+
+    // the sampled cosine basis of the module comment evaluated in f64
+    fn scaled_f64(n: usize, f: &[i32;64]) -> Vec<u8> {
+        let basis = |x: usize, u: usize| {
+            let cu = if u == 0 {core::f64::consts::FRAC_1_SQRT_2} else {1.0};
+            cu / 2.0 * ((2 * x + 1) as f64 * u as f64 * core::f64::consts::PI / (2 * n) as f64).cos()
+        };
+        let terms = n.min(8);
+        let mut out = vec![0_u8; n * n];
+        for y in 0..n {
+            for x in 0..n {
+                let mut val = 0.0;
+                for v in 0..terms {
+                    for u in 0..terms {
+                        val += basis(y, v) * basis(x, u) * f[v * 8 + u] as f64;
+                    }
+                }
+                out[y * n + x] = (val.round() as i32 + 128).clamp(0, 255) as u8;
+            }
+        }
+        out
+    }
+
+    #[test]
+    fn odd_and_large_sizes_are_the_sampled_basis() {
+        for n in [3, 12, 16] {
+            let scaled = ScaledIdct::new(n);
+            for f in random_corpus(1000, 300, 64) {
+                let (want, got) = (scaled_f64(n, &f), scaled.idct(&f));
+                for i in 0..n * n {
+                    assert!((want[i] as i32 - got[i] as i32).abs() <= 1, "n = {} block {:?}", n, f);
+                }
+            }
+        }
+    }
+}
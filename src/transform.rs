@@ -0,0 +1,211 @@
+// Common interface over the transforms in `idct` and `fdct`.
+//
+// Every algorithm is a zero sized type, so a decoder can be written as
+// `fn decode<T: InverseDct>(..)` and the algorithm swapped at the call site
+// without touching the inner loop.
+
+use crate::fdct;
+use crate::idct;
+
+// arithmetic the transform is computed in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Precision {
+    F32,
+    F64,
+    I32, // fixed point
+}
+
+// how the spatial (or frequency) values leave the transform
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Rounding {
+    Nearest,  // round() before the level shift
+    Truncate, // `as i32` after the level shift
+    None,     // raw f32 output (forward transforms)
+}
+
+// arithmetic cost of one 8x8 block
+// counts the butterfly / matrix arithmetic only, not the trigonometric calls,
+// the level shift or the clamp
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpCount {
+    pub mul: usize,
+    pub add: usize,
+}
+
+pub trait InverseDct {
+    const NAME: &'static str;
+    const PRECISION: Precision;
+    const ROUNDING: Rounding;
+    const OPS: OpCount;
+
+    // f is 64 dequantized coefficients in natural order f[v*8 + u]
+    // writes 64 level shifted samples, no allocation
+    fn idct_into(f: &[i32;64], out: &mut [u8;64]);
+
+    fn idct(f: &[i32]) -> Vec<u8> {
+        let mut out = [0_u8;64];
+        Self::idct_into(f[..64].try_into().unwrap(), &mut out);
+        out.to_vec()
+    }
+
+    // row y of the block is written to plane[offset + y * stride..][..8]
+    fn idct_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+        let mut out = [0_u8;64];
+        Self::idct_into(f, &mut out);
+        for (y, row) in out.chunks_exact(8).enumerate() {
+            plane[offset + y * stride..][..8].copy_from_slice(row);
+        }
+    }
+}
+
+pub trait ForwardDct {
+    const NAME: &'static str;
+    const PRECISION: Precision;
+    const ROUNDING: Rounding;
+    const OPS: OpCount;
+
+    // f is 64 samples, returns 64 coefficients in natural order
+    fn fdct(f: &[u8]) -> Vec<f32>;
+}
+
+macro_rules! inverse_dct {
+    ($t:ident, $func:path, $name:expr, $precision:ident, $rounding:ident, $mul:expr, $add:expr) => {
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $t;
+
+        impl InverseDct for $t {
+            const NAME: &'static str = $name;
+            const PRECISION: Precision = Precision::$precision;
+            const ROUNDING: Rounding = Rounding::$rounding;
+            const OPS: OpCount = OpCount { mul: $mul, add: $add };
+
+            #[inline]
+            fn idct_into(f: &[i32;64], out: &mut [u8;64]) {
+                $func(f, out)
+            }
+        }
+    };
+    // algorithms with a native strided store
+    ($t:ident, $func:path, $plane:path, $name:expr, $precision:ident, $rounding:ident, $mul:expr, $add:expr) => {
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $t;
+
+        impl InverseDct for $t {
+            const NAME: &'static str = $name;
+            const PRECISION: Precision = Precision::$precision;
+            const ROUNDING: Rounding = Rounding::$rounding;
+            const OPS: OpCount = OpCount { mul: $mul, add: $add };
+
+            #[inline]
+            fn idct_into(f: &[i32;64], out: &mut [u8;64]) {
+                $func(f, out)
+            }
+
+            #[inline]
+            fn idct_plane(f: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+                $plane(f, plane, offset, stride)
+            }
+        }
+    };
+}
+
+macro_rules! forward_dct {
+    ($t:ident, $func:path, $name:expr, $precision:ident, $rounding:ident, $mul:expr, $add:expr) => {
+        #[derive(Debug, Clone, Copy, Default)]
+        pub struct $t;
+
+        impl ForwardDct for $t {
+            const NAME: &'static str = $name;
+            const PRECISION: Precision = Precision::$precision;
+            const ROUNDING: Rounding = Rounding::$rounding;
+            const OPS: OpCount = OpCount { mul: $mul, add: $add };
+
+            #[inline]
+            fn fdct(f: &[u8]) -> Vec<f32> {
+                $func(f)
+            }
+        }
+    };
+}
+
+// direct T.81 A.3.3 sum: 64 outputs * 64 terms * (cu * cv * F * cos * cos)
+inverse_dct!(StandardIdct, idct::idct_into, "standard IDCT", F32, Nearest, 16384, 4096);
+inverse_dct!(StandardIdctF64, idct::idct_f64_into, "standard f64 IDCT", F64, Nearest, 16384, 4096);
+// 16 1D passes of 14 mul / 26 add + 64 final * 1/8
+inverse_dct!(LlmIdct, idct::llm_idct_into, idct::llm_idct_plane,
+    "LLM IDCT", F32, Truncate, 288, 416);
+// 16 1D passes of 8 prescale + 5 mul / 30 add
+inverse_dct!(AanIdct, idct::fast_idct_into, idct::fast_idct_plane,
+    "AAN IDCT", F32, Truncate, 208, 480);
+inverse_dct!(AanIdctF64, idct::fast_idct_f64_into, "AAN f64 IDCT", F64, Truncate, 208, 480);
+// jidctint: 16 1D passes of 12 mul / 32 add, rounding descale
+inverse_dct!(IslowIdct, idct::islow_idct_into, idct::islow_idct_plane,
+    "ISLOW IDCT", I32, Nearest, 192, 512);
+// 8 rows of 22 mul / 32 add + 8 columns of 6 mul / 26 add
+inverse_dct!(Ap922Idct, idct::ap922_idct_into, idct::ap922_idct_plane,
+    "AP922 IDCT", F32, Nearest, 224, 464);
+inverse_dct!(Idct7, idct::idct7_into, "IDCT method7", F32, Nearest, 2816, 2560);
+inverse_dct!(Idct8, idct::idct8_into, "IDCT method8", F32, Nearest, 2816, 2560);
+inverse_dct!(Idct9, idct::idct9_into, "IDCT method9", F32, Nearest, 1792, 1536);
+
+// 64 outputs * 64 terms * (f - 128) * cos * cos, + cu * cv / 4
+forward_dct!(StandardFdct, fdct::fdct, "Standard FDCT", F32, None, 8384, 8192);
+// 16 1D passes of 14 mul / 26 add + 64 final * 1/8
+forward_dct!(LlmFdct, fdct::llm_fdct, "LLM FDCT", F32, None, 288, 416);
+// jfdctflt / jfdctfst: 16 1D passes of 5 mul / 29 add + 64 final descale,
+// which an encoder folds into the quantization divisors
+forward_dct!(AanFdct, fdct::aan_fdct, "AAN FDCT", F32, None, 144, 464);
+forward_dct!(IfastFdct, fdct::ifast_fdct, "IFAST FDCT", I32, Truncate, 144, 464);
+forward_dct!(IslowFdct, fdct::islow_fdct, "ISLOW FDCT", I32, Nearest, 192, 512);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{random_corpus, Ieee1180Rand};
+
+    // idct_into, idct and idct_plane of T all give what func gives
+    fn check_inverse<T: InverseDct>(func: fn(&[i32;64], &mut [u8;64])) {
+        const STRIDE: usize = 13;
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in random_corpus(300, 300, 10).iter().chain(&random_corpus(300, 300, 64)) {
+            func(f, &mut want);
+            T::idct_into(f, &mut got);
+            assert_eq!(want, got, "{} idct_into, block {:?}", T::NAME, f);
+            assert_eq!(T::idct(f), want.to_vec(), "{} idct, block {:?}", T::NAME, f);
+            let mut plane = vec![0_u8; 7 * STRIDE + 5 + 8];
+            T::idct_plane(f, &mut plane, 5, STRIDE);
+            for y in 0..8 {
+                assert_eq!(&plane[5 + y * STRIDE..][..8], &want[y * 8..y * 8 + 8], "{} idct_plane", T::NAME);
+            }
+        }
+    }
+
+    fn check_forward<T: ForwardDct>(func: fn(&[u8]) -> Vec<f32>) {
+        let mut rand = Ieee1180Rand::new();
+        for _ in 0..500 {
+            let f: Vec<u8> = (0..64).map(|_| rand.next(0, 255) as u8).collect();
+            assert_eq!(T::fdct(&f), func(&f), "{} samples {:?}", T::NAME, f);
+        }
+    }
+
+    #[test]
+    fn types_wrap_their_functions() {
+        check_inverse::<StandardIdct>(idct::idct_into);
+        check_inverse::<StandardIdctF64>(idct::idct_f64_into);
+        check_inverse::<LlmIdct>(idct::llm_idct_into);
+        check_inverse::<AanIdct>(idct::fast_idct_into);
+        check_inverse::<AanIdctF64>(idct::fast_idct_f64_into);
+        check_inverse::<IslowIdct>(idct::islow_idct_into);
+        check_inverse::<Ap922Idct>(idct::ap922_idct_into);
+        check_inverse::<Idct7>(idct::idct7_into);
+        check_inverse::<Idct8>(idct::idct8_into);
+        check_inverse::<Idct9>(idct::idct9_into);
+
+        check_forward::<StandardFdct>(fdct::fdct);
+        check_forward::<LlmFdct>(fdct::llm_fdct);
+        check_forward::<AanFdct>(fdct::aan_fdct);
+        check_forward::<IfastFdct>(fdct::ifast_fdct);
+        check_forward::<IslowFdct>(fdct::islow_fdct);
+    }
+}
@@ -0,0 +1,235 @@
+// Rate-distortion optimized (trellis) quantization of the AC coefficients,
+// after mozjpeg's quantize_trellis
+//
+// Plain rounding gives the smallest error, trellis_quantize minimizes
+// error + lambda * bits instead: a coefficient can be rounded down a step
+// into a cheaper category, or zeroed so the run of zeros before the next
+// coefficient (or the EOB) absorbs it. The error is the squared difference
+// of the normalized coefficients, with an orthonormal DCT the same as the
+// squared sample error of the block. The bits are the Huffman code plus the
+// extra bits of every RRRRSSSS symbol and the EOB, with the code lengths of
+// the AC table the block will be coded with. The DC coefficient is rounded,
+// its cost depends on the neighbouring block.
+//
+// Dynamic programming over the zigzag positions: best[k] is the cheapest
+// coding of the coefficients up to k with k the last non zero one, tried
+// with rounding and rounding down for every position before it.
+
+use crate::fdct;
+use crate::huffman::category;
+use crate::zigzag::NATURAL_ORDER;
+
+const EOB: usize = 0x00;
+const ZRL: usize = 0xf0;
+
+// bits of one non zero coefficient v after run zeros, infinite when the
+// table has no code for the symbol
+fn symbol_bits(ac: &[u8;256], run: usize, v: i32) -> f64 {
+    let size = category(v) as usize;
+    let zrl = ac[ZRL] as usize * (run / 16);
+    let code = ac[(run % 16) << 4 | size] as usize;
+    if code == 0 || (run >= 16 && ac[ZRL] == 0) {
+        return f64::INFINITY;
+    }
+    (zrl + code + size) as f64
+}
+
+// AC bits of a quantized block (natural order) with the code lengths ac,
+// Huffman codes and extra bits
+pub fn ac_bits(f: &[i32;64], ac: &[u8;256]) -> u32 {
+    let mut bits = 0;
+    let mut run = 0;
+    for k in 1..64 {
+        let v = f[NATURAL_ORDER[k]];
+        if v == 0 {
+            run += 1;
+            continue;
+        }
+        bits += symbol_bits(ac, run, v) as u32;
+        run = 0;
+    }
+    if run > 0 {
+        bits += ac[EOB] as u32;
+    }
+    bits
+}
+
+// lambda for a table: at high rate a uniform quantizer with step q trades
+// about 2 ln 2 * q^2 / 12 of squared error per bit, q the mean AC step
+pub fn default_lambda(q: &[u16;64]) -> f32 {
+    let mean = q[1..].iter().map(|v| *v as f32).sum::<f32>() / 63.0;
+    2.0 * core::f32::consts::LN_2 * mean * mean / 12.0
+}
+
+// f is 64 normalized coefficients in natural order (llm_fdct output), q the
+// quantization table, ac the code lengths of the AC Huffman table
+// (HuffmanSpec::code_lengths) and lambda the squared error a bit is worth.
+// Quantized coefficients in natural order, like quant::quantize
+pub fn trellis_quantize(f: &[f32], q: &[u16;64], ac: &[u8;256], lambda: f32) -> [i32;64] {
+    let mut out = [0_i32;64];
+    out[0] = (f[0] / q[0] as f32).round() as i32;
+
+    // zeroed[k]: squared error of zeroing the coefficients 1..=k; the costs
+    // are sums of up to 64 terms and compared for near ties, f64
+    let lambda = lambda as f64;
+    let mut zeroed = [0_f64;64];
+    for k in 1..64 {
+        let c = f[NATURAL_ORDER[k]] as f64;
+        zeroed[k] = zeroed[k - 1] + c * c;
+    }
+
+    // best[0] is the start, nothing coded after the DC
+    let mut best = [f64::INFINITY;64];
+    let mut from = [0_usize;64];
+    let mut value = [0_i32;64];
+    best[0] = 0.0;
+    for k in 1..64 {
+        let i = NATURAL_ORDER[k];
+        let c = f[i];
+        let rounded = (c.abs() / q[i] as f32).round() as i32;
+        // rounding, then one step down, never to zero: that is a longer run
+        for m in [rounded, rounded - 1] {
+            if m < 1 {
+                continue;
+            }
+            let v = if c < 0.0 {-m} else {m};
+            let err = c as f64 - (v * q[i] as i32) as f64;
+            for j in 0..k {
+                if best[j] == f64::INFINITY {
+                    continue;
+                }
+                let run = k - j - 1;
+                let cost = best[j] + (zeroed[k - 1] - zeroed[j]) + err * err
+                    + lambda * symbol_bits(ac, run, v);
+                if cost < best[k] {
+                    best[k] = cost;
+                    from[k] = j;
+                    value[k] = v;
+                }
+            }
+        }
+    }
+
+    // last non zero coefficient e, zeros after it cost the EOB
+    let mut end = 0;
+    let mut end_cost = f64::INFINITY;
+    for (e, b) in best.iter().enumerate() {
+        let eob = if e < 63 {lambda * ac[EOB] as f64} else {0.0};
+        let cost = b + (zeroed[63] - zeroed[e]) + eob;
+        if cost < end_cost {
+            end_cost = cost;
+            end = e;
+        }
+    }
+    while end > 0 {
+        out[NATURAL_ORDER[end]] = value[end];
+        end = from[end];
+    }
+    out
+}
+
+// samples to trellis quantized coefficients, the encoder stage that
+// replaces quant::fdct_quantize
+pub fn fdct_trellis(f: &[u8], q: &[u16;64], ac: &[u8;256], lambda: f32) -> [i32;64] {
+    trellis_quantize(&fdct::llm_fdct(f), q, ac, lambda)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::Ieee1180Rand;
+    use crate::huffman::HuffmanSpec;
+    use crate::quant::{self, LUMINANCE};
+    use crate::transform::{LlmFdct, LlmIdct};
+
+    // smooth gradients, edges and noise
+    fn blocks() -> Vec<Vec<u8>> {
+        let mut rand = Ieee1180Rand::new();
+        let mut blocks = Vec::new();
+        for n in 0..300 {
+            let (a, b) = (rand.next(6, 6), rand.next(6, 6));
+            let base = rand.next(-40, 210);
+            let noise = [0, 2, 8, 30][n % 4];
+            blocks.push((0..64).map(|i| {
+                let (x, y) = (i % 8, i / 8);
+                let edge = if n % 3 == 0 && x + y > 7 {40} else {0};
+                (base + a * x + b * y + edge + rand.next(noise, noise)).clamp(0, 255) as u8
+            }).collect());
+        }
+        blocks
+    }
+
+    fn squared_error(f: &[f32], q: &[u16;64], c: &[i32;64]) -> f32 {
+        (0..64).map(|i| (f[i] - (c[i] * q[i] as i32) as f32).powi(2)).sum()
+    }
+
+    // the rounded block is one of the paths searched
+    #[test]
+    fn never_costlier_than_rounding() {
+        let ac = HuffmanSpec::ac_luminance().code_lengths();
+        for quality in [30, 75, 95] {
+            let q = quant::quality_table(&LUMINANCE, quality);
+            let lambda = default_lambda(&q);
+            for f in blocks() {
+                let coefficients = fdct::llm_fdct(&f);
+                let rounded = quant::quantize(&coefficients, &q);
+                let trellis = trellis_quantize(&coefficients, &q, &ac, lambda);
+                let cost = |c: &[i32;64]| squared_error(&coefficients, &q, c) + lambda * ac_bits(c, &ac) as f32;
+                assert!(cost(&trellis) <= cost(&rounded) * 1.0001 + 1e-3,
+                    "{} vs {}, samples {:?}", cost(&trellis), cost(&rounded), f);
+                assert_eq!(trellis[0], rounded[0]);
+            }
+        }
+    }
+
+    // without a rate term the search keeps the smallest error, the rounded
+    // one up to ties like 0.5 * q rounded to 0 instead of 1
+    #[test]
+    fn lambda_zero_keeps_rounding_error() {
+        let ac = HuffmanSpec::ac_luminance().code_lengths();
+        let q = quant::quality_table(&LUMINANCE, 75);
+        for f in blocks() {
+            let coefficients = fdct::llm_fdct(&f);
+            let trellis = trellis_quantize(&coefficients, &q, &ac, 0.0);
+            let rounded = quant::quantize(&coefficients, &q);
+            let (a, b) = (squared_error(&coefficients, &q, &trellis), squared_error(&coefficients, &q, &rounded));
+            assert!((a - b).abs() <= b * 1e-5, "{} vs {}, samples {:?}", a, b, f);
+        }
+    }
+
+    // total AC bits and PSNR of the decoded blocks
+    fn encode(blocks: &[Vec<u8>], quantize: impl Fn(&[u8]) -> [i32;64], q: &[u16;64], ac: &[u8;256]) -> (u32, f64) {
+        let mut bits = 0;
+        let mut sse = 0.0;
+        let mut out = [0_u8;64];
+        for f in blocks {
+            let c = quantize(f);
+            bits += ac_bits(&c, ac);
+            quant::dequantize_idct::<LlmIdct>(&c, q, &mut out);
+            sse += f.iter().zip(&out).map(|(a, b)| (*a as f64 - *b as f64).powi(2)).sum::<f64>();
+        }
+        let mse = sse / (blocks.len() * 64) as f64;
+        (bits, 10.0 * (255.0 * 255.0 / mse).log10())
+    }
+
+    // trellis at quality 85 against plain rounding at the quality with the
+    // same or lower PSNR: fewer bits for the same quality
+    #[test]
+    fn saves_bits_at_equal_psnr() {
+        let ac = HuffmanSpec::ac_luminance().code_lengths();
+        let blocks = blocks();
+        let q = quant::quality_table(&LUMINANCE, 85);
+        let (trellis_bits, trellis_psnr) = encode(&blocks, |f| fdct_trellis(f, &q, &ac, default_lambda(&q)), &q, &ac);
+
+        // highest plain quality whose PSNR does not beat trellis
+        let (plain_bits, plain_psnr) = (1..=100).rev()
+            .map(|quality| {
+                let q = quant::quality_table(&LUMINANCE, quality);
+                encode(&blocks, |f| quant::fdct_quantize::<LlmFdct>(f, &q), &q, &ac)
+            })
+            .find(|(_, psnr)| *psnr <= trellis_psnr)
+            .unwrap();
+        assert!(trellis_bits < plain_bits,
+            "trellis {} bits {:.2} dB, rounding {} bits {:.2} dB", trellis_bits, trellis_psnr, plain_bits, plain_psnr);
+    }
+}
@@ -0,0 +1,623 @@
+// SSE2 and AVX2 versions of llm_idct and islow_idct, all 8 rows at once
+//
+// Float LL&M: one vector per coefficient index with the rows in the lanes
+// (4 of them for SSE2, 8 for AVX2), the llm_1d pass of idct.rs and a
+// register transpose between the passes. No FMA and the same operation
+// order, so the output is bit identical to llm_idct_into.
+//
+// Integer islow: the rows are already vectors over the columns, pass 1 runs
+// without a transpose. SSE2 keeps 16 bit lanes like libjpeg-turbo's
+// jidctint-sse2 and multiplies with pmaddwd, the odd part is regrouped so
+// each product pair is one madd. The regrouping is exact in integers as
+// long as the coefficients and the pass 1 results fit in i16 and nothing
+// overflows i32. A 1D pass multiplies by at most 61214 / 2^13 (the largest
+// row sum of |constants|), so coefficients within ±ISLOW_SSE2_MAX keep the
+// pass 1 results in i16 and pass 2 below 2^31. That covers the
+// coefficients of 8 bit samples; blocks outside go to islow_idct_into.
+// AVX2 keeps 32 bit lanes: wrapping only changes bits above the ones the
+// final descale and range limit keep, except in pass 1, which is exact
+// for i16 coefficients (ISLOW_AVX2_MAX). Both match islow_idct_into for
+// any input.
+
+use core::arch::x86_64::*;
+use core::ops::{Add, Mul, Sub};
+
+use crate::idct::{
+    self, llm_1d, Lanes, CONST_BITS, PASS1_BITS,
+    FIX_0_298631336, FIX_0_390180644, FIX_0_541196100, FIX_0_765366865,
+    FIX_0_899976223, FIX_1_175875602, FIX_1_501321110, FIX_1_847759065,
+    FIX_1_961570560, FIX_2_053119869, FIX_2_562915447, FIX_3_072711026,
+};
+
+// f32 lanes for the float butterfly
+#[derive(Clone, Copy)]
+struct F32x4(__m128);
+
+impl Add for F32x4 {
+    type Output = Self;
+    #[inline(always)]
+    fn add(self, b: Self) -> Self {
+        unsafe { F32x4(_mm_add_ps(self.0, b.0)) }
+    }
+}
+
+impl Sub for F32x4 {
+    type Output = Self;
+    #[inline(always)]
+    fn sub(self, b: Self) -> Self {
+        unsafe { F32x4(_mm_sub_ps(self.0, b.0)) }
+    }
+}
+
+impl Mul for F32x4 {
+    type Output = Self;
+    #[inline(always)]
+    fn mul(self, b: Self) -> Self {
+        unsafe { F32x4(_mm_mul_ps(self.0, b.0)) }
+    }
+}
+
+impl Lanes for F32x4 {
+    #[inline(always)]
+    fn splat(x: f32) -> Self {
+        unsafe { F32x4(_mm_set1_ps(x)) }
+    }
+}
+
+// only built inside avx2 functions
+#[derive(Clone, Copy)]
+struct F32x8(__m256);
+
+impl Add for F32x8 {
+    type Output = Self;
+    #[inline(always)]
+    fn add(self, b: Self) -> Self {
+        unsafe { F32x8(_mm256_add_ps(self.0, b.0)) }
+    }
+}
+
+impl Sub for F32x8 {
+    type Output = Self;
+    #[inline(always)]
+    fn sub(self, b: Self) -> Self {
+        unsafe { F32x8(_mm256_sub_ps(self.0, b.0)) }
+    }
+}
+
+impl Mul for F32x8 {
+    type Output = Self;
+    #[inline(always)]
+    fn mul(self, b: Self) -> Self {
+        unsafe { F32x8(_mm256_mul_ps(self.0, b.0)) }
+    }
+}
+
+impl Lanes for F32x8 {
+    #[inline(always)]
+    fn splat(x: f32) -> Self {
+        unsafe { F32x8(_mm256_set1_ps(x)) }
+    }
+}
+
+#[inline(always)]
+unsafe fn transpose4_ps(a: __m128, b: __m128, c: __m128, d: __m128) -> [__m128;4] {
+    let t0 = _mm_unpacklo_ps(a, b);
+    let t1 = _mm_unpacklo_ps(c, d);
+    let t2 = _mm_unpackhi_ps(a, b);
+    let t3 = _mm_unpackhi_ps(c, d);
+    [_mm_movelh_ps(t0, t1), _mm_movehl_ps(t1, t0), _mm_movelh_ps(t2, t3), _mm_movehl_ps(t3, t2)]
+}
+
+// f is 64 coefficients, vals 64 level shifted samples like llm_idct_into
+pub fn llm_idct_sse2(f: &[i32;64], vals: &mut [u8;64]) {
+    unsafe {
+        // q[r][h] is columns 4h..4h + 3 of row r
+        let mut q = [[_mm_setzero_ps();2];8];
+        for (r, row) in q.iter_mut().enumerate() {
+            let p = f.as_ptr().add(r * 8) as *const __m128i;
+            row[0] = _mm_cvtepi32_ps(_mm_loadu_si128(p));
+            row[1] = _mm_cvtepi32_ps(_mm_loadu_si128(p.add(1)));
+        }
+
+        // pass 1: rows 0..3 then 4..7 in the lanes, one vector per u
+        let mut t = [[_mm_setzero_ps();8];2];
+        for (g, out) in t.iter_mut().enumerate() {
+            let r = g * 4;
+            let lo = transpose4_ps(q[r][0], q[r + 1][0], q[r + 2][0], q[r + 3][0]);
+            let hi = transpose4_ps(q[r][1], q[r + 1][1], q[r + 2][1], q[r + 3][1]);
+            let x = [lo[0], lo[1], lo[2], lo[3], hi[0], hi[1], hi[2], hi[3]].map(F32x4);
+            *out = llm_1d(x).map(|v| v.0);
+        }
+
+        // pass 2: columns 0..3 then 4..7 in the lanes, one vector per v
+        let m0 = F32x4::splat(0.125);
+        // o[h][y] is columns 4h..4h + 3 of output row y
+        let mut o = [[_mm_setzero_ps();8];2];
+        for (h, half) in o.iter_mut().enumerate() {
+            let u = h * 4;
+            let top = transpose4_ps(t[0][u], t[0][u + 1], t[0][u + 2], t[0][u + 3]);
+            let bottom = transpose4_ps(t[1][u], t[1][u + 1], t[1][u + 2], t[1][u + 3]);
+            let x = [top[0], top[1], top[2], top[3], bottom[0], bottom[1], bottom[2], bottom[3]].map(F32x4);
+            *half = llm_1d(x).map(|v| (v * m0).0);
+        }
+
+        // store_truncate: + 128, truncate, saturate to 0..255
+        let c128 = _mm_set1_ps(128.0);
+        for y in (0..8).step_by(2) {
+            let a = _mm_packs_epi32(
+                _mm_cvttps_epi32(_mm_add_ps(o[0][y], c128)),
+                _mm_cvttps_epi32(_mm_add_ps(o[1][y], c128)));
+            let b = _mm_packs_epi32(
+                _mm_cvttps_epi32(_mm_add_ps(o[0][y + 1], c128)),
+                _mm_cvttps_epi32(_mm_add_ps(o[1][y + 1], c128)));
+            _mm_storeu_si128(vals.as_mut_ptr().add(y * 8) as *mut __m128i, _mm_packus_epi16(a, b));
+        }
+    }
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn transpose8_ps(r: &[__m256;8]) -> [__m256;8] {
+    let t0 = _mm256_unpacklo_ps(r[0], r[1]);
+    let t1 = _mm256_unpackhi_ps(r[0], r[1]);
+    let t2 = _mm256_unpacklo_ps(r[2], r[3]);
+    let t3 = _mm256_unpackhi_ps(r[2], r[3]);
+    let t4 = _mm256_unpacklo_ps(r[4], r[5]);
+    let t5 = _mm256_unpackhi_ps(r[4], r[5]);
+    let t6 = _mm256_unpacklo_ps(r[6], r[7]);
+    let t7 = _mm256_unpackhi_ps(r[6], r[7]);
+    let s0 = _mm256_shuffle_ps(t0, t2, 0x44);
+    let s1 = _mm256_shuffle_ps(t0, t2, 0xee);
+    let s2 = _mm256_shuffle_ps(t1, t3, 0x44);
+    let s3 = _mm256_shuffle_ps(t1, t3, 0xee);
+    let s4 = _mm256_shuffle_ps(t4, t6, 0x44);
+    let s5 = _mm256_shuffle_ps(t4, t6, 0xee);
+    let s6 = _mm256_shuffle_ps(t5, t7, 0x44);
+    let s7 = _mm256_shuffle_ps(t5, t7, 0xee);
+    [
+        _mm256_permute2f128_ps(s0, s4, 0x20),
+        _mm256_permute2f128_ps(s1, s5, 0x20),
+        _mm256_permute2f128_ps(s2, s6, 0x20),
+        _mm256_permute2f128_ps(s3, s7, 0x20),
+        _mm256_permute2f128_ps(s0, s4, 0x31),
+        _mm256_permute2f128_ps(s1, s5, 0x31),
+        _mm256_permute2f128_ps(s2, s6, 0x31),
+        _mm256_permute2f128_ps(s3, s7, 0x31),
+    ]
+}
+
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn transpose8_epi32(r: &[__m256i;8]) -> [__m256i;8] {
+    let t0 = _mm256_unpacklo_epi32(r[0], r[1]);
+    let t1 = _mm256_unpackhi_epi32(r[0], r[1]);
+    let t2 = _mm256_unpacklo_epi32(r[2], r[3]);
+    let t3 = _mm256_unpackhi_epi32(r[2], r[3]);
+    let t4 = _mm256_unpacklo_epi32(r[4], r[5]);
+    let t5 = _mm256_unpackhi_epi32(r[4], r[5]);
+    let t6 = _mm256_unpacklo_epi32(r[6], r[7]);
+    let t7 = _mm256_unpackhi_epi32(r[6], r[7]);
+    let s0 = _mm256_unpacklo_epi64(t0, t2);
+    let s1 = _mm256_unpackhi_epi64(t0, t2);
+    let s2 = _mm256_unpacklo_epi64(t1, t3);
+    let s3 = _mm256_unpackhi_epi64(t1, t3);
+    let s4 = _mm256_unpacklo_epi64(t4, t6);
+    let s5 = _mm256_unpackhi_epi64(t4, t6);
+    let s6 = _mm256_unpacklo_epi64(t5, t7);
+    let s7 = _mm256_unpackhi_epi64(t5, t7);
+    [
+        _mm256_permute2x128_si256(s0, s4, 0x20),
+        _mm256_permute2x128_si256(s1, s5, 0x20),
+        _mm256_permute2x128_si256(s2, s6, 0x20),
+        _mm256_permute2x128_si256(s3, s7, 0x20),
+        _mm256_permute2x128_si256(s0, s4, 0x31),
+        _mm256_permute2x128_si256(s1, s5, 0x31),
+        _mm256_permute2x128_si256(s2, s6, 0x31),
+        _mm256_permute2x128_si256(s3, s7, 0x31),
+    ]
+}
+
+// 8 rows of 8 i32 in 0..255 (or saturating) to 64 bytes
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn store8_epi32(r: &[__m256i;8], vals: &mut [u8;64]) {
+    // packs works per 128 bit lane, the permute puts each row back together
+    let order = _mm256_setr_epi32(0, 4, 1, 5, 2, 6, 3, 7);
+    for i in 0..2 {
+        let a = _mm256_packs_epi32(r[i * 4], r[i * 4 + 1]);
+        let b = _mm256_packs_epi32(r[i * 4 + 2], r[i * 4 + 3]);
+        let bytes = _mm256_permutevar8x32_epi32(_mm256_packus_epi16(a, b), order);
+        _mm256_storeu_si256(vals.as_mut_ptr().add(i * 32) as *mut __m256i, bytes);
+    }
+}
+
+/// llm_idct_into with AVX2
+///
+/// # Safety
+/// The CPU must support AVX2, see `is_x86_feature_detected!("avx2")`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn llm_idct_avx2(f: &[i32;64], vals: &mut [u8;64]) {
+    let mut r = [_mm256_setzero_ps();8];
+    for (y, row) in r.iter_mut().enumerate() {
+        *row = _mm256_cvtepi32_ps(_mm256_loadu_si256(f.as_ptr().add(y * 8) as *const __m256i));
+    }
+
+    // pass 1: the 8 rows in the lanes, one vector per u
+    let x = transpose8_ps(&r).map(F32x8);
+    let t = llm_1d(x).map(|v| v.0);
+
+    // pass 2: the 8 columns in the lanes, one vector per v
+    let x = transpose8_ps(&t).map(F32x8);
+    let m0 = F32x8::splat(0.125);
+    let o = llm_1d(x).map(|v| (v * m0).0);
+
+    let c128 = _mm256_set1_ps(128.0);
+    store8_epi32(&o.map(|v| _mm256_cvttps_epi32(_mm256_add_ps(v, c128))), vals);
+}
+
+// 16 bit constant pairs for pmaddwd: (a, b) * (x, y) = a * x + b * y
+// even part: z1 = (z2 + z3) * c0541 expanded into the two products
+const EVEN_TMP3: (i32, i32) = (FIX_0_541196100 + FIX_0_765366865, FIX_0_541196100);
+const EVEN_TMP2: (i32, i32) = (FIX_0_541196100, FIX_0_541196100 - FIX_1_847759065);
+// odd part with z1..z5 expanded, (F1, F7) and (F3, F5) pairs
+const ODD_TMP0: [(i32, i32);2] = [
+    (FIX_1_175875602 - FIX_0_899976223,
+     FIX_0_298631336 - FIX_0_899976223 - FIX_1_961570560 + FIX_1_175875602),
+    (FIX_1_175875602 - FIX_1_961570560, FIX_1_175875602)];
+const ODD_TMP1: [(i32, i32);2] = [
+    (FIX_1_175875602 - FIX_0_390180644, FIX_1_175875602),
+    (FIX_1_175875602 - FIX_2_562915447,
+     FIX_2_053119869 - FIX_2_562915447 - FIX_0_390180644 + FIX_1_175875602)];
+const ODD_TMP2: [(i32, i32);2] = [
+    (FIX_1_175875602, FIX_1_175875602 - FIX_1_961570560),
+    (FIX_3_072711026 - FIX_2_562915447 - FIX_1_961570560 + FIX_1_175875602,
+     FIX_1_175875602 - FIX_2_562915447)];
+const ODD_TMP3: [(i32, i32);2] = [
+    (FIX_1_501321110 - FIX_0_899976223 - FIX_0_390180644 + FIX_1_175875602,
+     FIX_1_175875602 - FIX_0_899976223),
+    (FIX_1_175875602, FIX_1_175875602 - FIX_0_390180644)];
+
+#[inline(always)]
+unsafe fn pair(c: (i32, i32)) -> __m128i {
+    let (a, b) = (c.0 as i16, c.1 as i16);
+    _mm_setr_epi16(a, b, a, b, a, b, a, b)
+}
+
+// x * 2^13 of the low and high 4 i16 lanes as i32
+#[inline(always)]
+unsafe fn widen_const_bits(x: __m128i) -> [__m128i;2] {
+    // x in the high half of each i32, shifted down to 13 fraction bits
+    let z = _mm_setzero_si128();
+    [_mm_srai_epi32::<{ 16 - CONST_BITS }>(_mm_unpacklo_epi16(z, x)),
+     _mm_srai_epi32::<{ 16 - CONST_BITS }>(_mm_unpackhi_epi16(z, x))]
+}
+
+// one islow 1D pass over 8 vectors of 8 i16, results descaled by n
+// and packed back to i16 (saturating)
+#[inline(always)]
+unsafe fn islow_1d_sse2(x: &[__m128i;8], n: i32) -> [[__m128i;2];8] {
+    // even part
+    let p26 = [_mm_unpacklo_epi16(x[2], x[6]), _mm_unpackhi_epi16(x[2], x[6])];
+    let tmp3 = p26.map(|p| _mm_madd_epi16(p, pair(EVEN_TMP3)));
+    let tmp2 = p26.map(|p| _mm_madd_epi16(p, pair(EVEN_TMP2)));
+
+    let z2 = widen_const_bits(x[0]);
+    let z3 = widen_const_bits(x[4]);
+
+    // odd part, each output is (F1, F7) . c[0] + (F3, F5) . c[1]
+    let p17 = [_mm_unpacklo_epi16(x[1], x[7]), _mm_unpackhi_epi16(x[1], x[7])];
+    let p35 = [_mm_unpacklo_epi16(x[3], x[5]), _mm_unpackhi_epi16(x[3], x[5])];
+    let odd = |c: [(i32, i32);2], h: usize| {
+        _mm_add_epi32(_mm_madd_epi16(p17[h], pair(c[0])), _mm_madd_epi16(p35[h], pair(c[1])))
+    };
+
+    let round = _mm_set1_epi32(1 << (n - 1));
+    let shift = _mm_cvtsi32_si128(n);
+    let descale = |v: __m128i| _mm_sra_epi32(_mm_add_epi32(v, round), shift);
+
+    let mut out = [[_mm_setzero_si128();2];8];
+    for h in 0..2 {
+        let tmp0 = _mm_add_epi32(z2[h], z3[h]);
+        let tmp1 = _mm_sub_epi32(z2[h], z3[h]);
+
+        let tmp10 = _mm_add_epi32(tmp0, tmp3[h]);
+        let tmp13 = _mm_sub_epi32(tmp0, tmp3[h]);
+        let tmp11 = _mm_add_epi32(tmp1, tmp2[h]);
+        let tmp12 = _mm_sub_epi32(tmp1, tmp2[h]);
+
+        let o0 = odd(ODD_TMP0, h);
+        let o1 = odd(ODD_TMP1, h);
+        let o2 = odd(ODD_TMP2, h);
+        let o3 = odd(ODD_TMP3, h);
+
+        out[0][h] = descale(_mm_add_epi32(tmp10, o3));
+        out[7][h] = descale(_mm_sub_epi32(tmp10, o3));
+        out[1][h] = descale(_mm_add_epi32(tmp11, o2));
+        out[6][h] = descale(_mm_sub_epi32(tmp11, o2));
+        out[2][h] = descale(_mm_add_epi32(tmp12, o1));
+        out[5][h] = descale(_mm_sub_epi32(tmp12, o1));
+        out[3][h] = descale(_mm_add_epi32(tmp13, o0));
+        out[4][h] = descale(_mm_sub_epi32(tmp13, o0));
+    }
+    out
+}
+
+#[inline(always)]
+unsafe fn transpose8_epi16(r: &[__m128i;8]) -> [__m128i;8] {
+    let t0 = _mm_unpacklo_epi16(r[0], r[1]);
+    let t1 = _mm_unpackhi_epi16(r[0], r[1]);
+    let t2 = _mm_unpacklo_epi16(r[2], r[3]);
+    let t3 = _mm_unpackhi_epi16(r[2], r[3]);
+    let t4 = _mm_unpacklo_epi16(r[4], r[5]);
+    let t5 = _mm_unpackhi_epi16(r[4], r[5]);
+    let t6 = _mm_unpacklo_epi16(r[6], r[7]);
+    let t7 = _mm_unpackhi_epi16(r[6], r[7]);
+    let u0 = _mm_unpacklo_epi32(t0, t2);
+    let u1 = _mm_unpackhi_epi32(t0, t2);
+    let u2 = _mm_unpacklo_epi32(t1, t3);
+    let u3 = _mm_unpackhi_epi32(t1, t3);
+    let u4 = _mm_unpacklo_epi32(t4, t6);
+    let u5 = _mm_unpackhi_epi32(t4, t6);
+    let u6 = _mm_unpacklo_epi32(t5, t7);
+    let u7 = _mm_unpackhi_epi32(t5, t7);
+    [
+        _mm_unpacklo_epi64(u0, u4),
+        _mm_unpackhi_epi64(u0, u4),
+        _mm_unpacklo_epi64(u1, u5),
+        _mm_unpackhi_epi64(u1, u5),
+        _mm_unpacklo_epi64(u2, u6),
+        _mm_unpackhi_epi64(u2, u6),
+        _mm_unpacklo_epi64(u3, u7),
+        _mm_unpackhi_epi64(u3, u7),
+    ]
+}
+
+// largest |coefficient| the SIMD islow passes handle exactly, see the
+// top of the file: 32767 * 2^11 / 61214 and i16
+const ISLOW_SSE2_MAX: u32 = 1096;
+const ISLOW_AVX2_MAX: u32 = 32768;
+
+#[inline(always)]
+fn in_range(f: &[i32;64], max: u32) -> bool {
+    f.iter().all(|c| c.unsigned_abs() <= max)
+}
+
+// f is 64 dequantized coefficients, vals 64 samples like islow_idct_into
+// blocks with a coefficient beyond ±ISLOW_SSE2_MAX take the scalar path
+pub fn islow_idct_sse2(f: &[i32;64], vals: &mut [u8;64]) {
+    if !in_range(f, ISLOW_SSE2_MAX) {
+        return idct::islow_idct_into(f, vals);
+    }
+    unsafe {
+        let mut r = [_mm_setzero_si128();8];
+        for (y, row) in r.iter_mut().enumerate() {
+            let p = f.as_ptr().add(y * 8) as *const __m128i;
+            *row = _mm_packs_epi32(_mm_loadu_si128(p), _mm_loadu_si128(p.add(1)));
+        }
+
+        // pass 1: the columns are the lanes already
+        let ws = islow_1d_sse2(&r, CONST_BITS - PASS1_BITS).map(|v| _mm_packs_epi32(v[0], v[1]));
+
+        // pass 2: rows in the lanes
+        let o = islow_1d_sse2(&transpose8_epi16(&ws), CONST_BITS + PASS1_BITS + 3);
+
+        // range_limit: j = (x + 128) & 1023 is the sample below 256,
+        // 255 up to 639 (packus saturates) and 0 above
+        let c128 = _mm_set1_epi32(128);
+        let mask = _mm_set1_epi32(1023);
+        let c639 = _mm_set1_epi16(639);
+        let limited = o.map(|v| {
+            let lo = _mm_and_si128(_mm_add_epi32(v[0], c128), mask);
+            let hi = _mm_and_si128(_mm_add_epi32(v[1], c128), mask);
+            let j = _mm_packs_epi32(lo, hi);
+            _mm_andnot_si128(_mm_cmpgt_epi16(j, c639), j)
+        });
+        let rows = transpose8_epi16(&limited);
+        for y in (0..8).step_by(2) {
+            _mm_storeu_si128(vals.as_mut_ptr().add(y * 8) as *mut __m128i, _mm_packus_epi16(rows[y], rows[y + 1]));
+        }
+    }
+}
+
+// one islow 1D pass over 8 vectors of 8 i32, the islow_idct expressions
+#[inline]
+#[target_feature(enable = "avx2")]
+unsafe fn islow_1d_avx2(f: &[__m256i;8], n: i32) -> [__m256i;8] {
+    let c = |x: i32| _mm256_set1_epi32(x);
+    let add = |a, b| _mm256_add_epi32(a, b);
+    let sub = |a, b| _mm256_sub_epi32(a, b);
+    let mul = |a, b| _mm256_mullo_epi32(a, b);
+
+    // even part
+    let z2 = f[2];
+    let z3 = f[6];
+    let z1 = mul(add(z2, z3), c(FIX_0_541196100));
+    let tmp2 = add(z1, mul(z3, c(-FIX_1_847759065)));
+    let tmp3 = add(z1, mul(z2, c(FIX_0_765366865)));
+
+    let z2 = f[0];
+    let z3 = f[4];
+    let tmp0 = _mm256_slli_epi32::<CONST_BITS>(add(z2, z3));
+    let tmp1 = _mm256_slli_epi32::<CONST_BITS>(sub(z2, z3));
+
+    let tmp10 = add(tmp0, tmp3);
+    let tmp13 = sub(tmp0, tmp3);
+    let tmp11 = add(tmp1, tmp2);
+    let tmp12 = sub(tmp1, tmp2);
+
+    // odd part
+    let tmp0 = f[7];
+    let tmp1 = f[5];
+    let tmp2 = f[3];
+    let tmp3 = f[1];
+
+    let z1 = add(tmp0, tmp3);
+    let z2 = add(tmp1, tmp2);
+    let z3 = add(tmp0, tmp2);
+    let z4 = add(tmp1, tmp3);
+    let z5 = mul(add(z3, z4), c(FIX_1_175875602));
+
+    let tmp0 = mul(tmp0, c(FIX_0_298631336));
+    let tmp1 = mul(tmp1, c(FIX_2_053119869));
+    let tmp2 = mul(tmp2, c(FIX_3_072711026));
+    let tmp3 = mul(tmp3, c(FIX_1_501321110));
+    let z1 = mul(z1, c(-FIX_0_899976223));
+    let z2 = mul(z2, c(-FIX_2_562915447));
+    let z3 = add(mul(z3, c(-FIX_1_961570560)), z5);
+    let z4 = add(mul(z4, c(-FIX_0_390180644)), z5);
+
+    let tmp0 = add(add(tmp0, z1), z3);
+    let tmp1 = add(add(tmp1, z2), z4);
+    let tmp2 = add(add(tmp2, z2), z3);
+    let tmp3 = add(add(tmp3, z1), z4);
+
+    let round = c(1 << (n - 1));
+    let shift = _mm_cvtsi32_si128(n);
+    let descale = |v| _mm256_sra_epi32(add(v, round), shift);
+    [
+        descale(add(tmp10, tmp3)),
+        descale(add(tmp11, tmp2)),
+        descale(add(tmp12, tmp1)),
+        descale(add(tmp13, tmp0)),
+        descale(sub(tmp13, tmp0)),
+        descale(sub(tmp12, tmp1)),
+        descale(sub(tmp11, tmp2)),
+        descale(sub(tmp10, tmp3)),
+    ]
+}
+
+/// islow_idct_into with AVX2, blocks beyond ±32768 take the scalar path
+///
+/// # Safety
+/// The CPU must support AVX2, see `is_x86_feature_detected!("avx2")`.
+#[target_feature(enable = "avx2")]
+pub unsafe fn islow_idct_avx2(f: &[i32;64], vals: &mut [u8;64]) {
+    if !in_range(f, ISLOW_AVX2_MAX) {
+        return idct::islow_idct_into(f, vals);
+    }
+    let mut r = [_mm256_setzero_si256();8];
+    for (y, row) in r.iter_mut().enumerate() {
+        *row = _mm256_loadu_si256(f.as_ptr().add(y * 8) as *const __m256i);
+    }
+
+    // pass 1: the columns are the lanes already
+    let ws = islow_1d_avx2(&r, CONST_BITS - PASS1_BITS);
+
+    // pass 2: rows in the lanes
+    let o = islow_1d_avx2(&transpose8_epi32(&ws), CONST_BITS + PASS1_BITS + 3);
+
+    // range_limit as in islow_idct_sse2
+    let c128 = _mm256_set1_epi32(128);
+    let mask = _mm256_set1_epi32(1023);
+    let c639 = _mm256_set1_epi32(639);
+    let limited = o.map(|v| {
+        let j = _mm256_and_si256(_mm256_add_epi32(v, c128), mask);
+        _mm256_andnot_si256(_mm256_cmpgt_epi32(j, c639), j)
+    });
+    store8_epi32(&transpose8_epi32(&limited), vals);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{ieee1180_corpus, random_corpus};
+
+    fn corpus() -> Vec<[i32;64]> {
+        let mut corpus = Vec::new();
+        for (l, h) in [(256, 255), (5, 5), (300, 300)] {
+            corpus.extend(ieee1180_corpus(2000, l, h, 1));
+            corpus.extend(ieee1180_corpus(2000, l, h, -1));
+        }
+        for nonzero in [1, 3, 10, 64] {
+            corpus.extend(random_corpus(2000, 1000, nonzero));
+        }
+        corpus
+    }
+
+    // coefficients far out of the 8 bit sample range
+    fn large_corpus() -> Vec<[i32;64]> {
+        random_corpus(5000, 2047, 64)
+    }
+
+    // ±m with the signs of the basis functions of each output sample, the
+    // largest pass results for that bound, and all m
+    fn extreme_blocks(m: i32) -> Vec<[i32;64]> {
+        let sign = |u: usize, x: usize| {
+            let c = ((2 * x + 1) as f64 * u as f64 * core::f64::consts::PI / 16.0).cos();
+            if c < 0.0 { -1 } else { 1 }
+        };
+        let mut blocks = Vec::new();
+        for y in 0..8 {
+            for x in 0..8 {
+                let f: [i32;64] = core::array::from_fn(|k| m * sign(k / 8, y) * sign(k % 8, x));
+                blocks.push(f);
+                blocks.push(f.map(|c| -c));
+            }
+        }
+        blocks.push([m;64]);
+        blocks.push([-m;64]);
+        blocks
+    }
+
+    #[test]
+    fn llm_idct_sse2_matches_scalar() {
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in corpus().iter().chain(large_corpus().iter()) {
+            idct::llm_idct_into(f, &mut want);
+            llm_idct_sse2(f, &mut got);
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+
+    #[test]
+    fn llm_idct_avx2_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in corpus().iter().chain(large_corpus().iter()) {
+            idct::llm_idct_into(f, &mut want);
+            unsafe { llm_idct_avx2(f, &mut got) };
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+
+    #[test]
+    fn islow_idct_sse2_matches_scalar() {
+        let mut corpus = corpus();
+        corpus.extend(large_corpus());
+        // the SIMD bound, one past it and i16 / i32 sized blocks
+        for m in [ISLOW_SSE2_MAX as i32, ISLOW_SSE2_MAX as i32 + 1, 32767, 1 << 24] {
+            corpus.extend(extreme_blocks(m));
+        }
+        assert!(corpus.iter().any(|f| in_range(f, ISLOW_SSE2_MAX) && !in_range(f, 1023)));
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in &corpus {
+            idct::islow_idct_into(f, &mut want);
+            islow_idct_sse2(f, &mut got);
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+
+    #[test]
+    fn islow_idct_avx2_matches_scalar() {
+        if !is_x86_feature_detected!("avx2") {
+            return;
+        }
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        let mut corpus = corpus();
+        corpus.extend(large_corpus());
+        for m in [ISLOW_SSE2_MAX as i32, ISLOW_AVX2_MAX as i32, ISLOW_AVX2_MAX as i32 + 1, 1 << 24] {
+            corpus.extend(extreme_blocks(m));
+        }
+        for f in &corpus {
+            idct::islow_idct_into(f, &mut want);
+            unsafe { islow_idct_avx2(f, &mut got) };
+            assert_eq!(want, got, "block {:?}", f);
+        }
+    }
+}
@@ -0,0 +1,297 @@
+// Zigzag order (T.81 Figure A.6) for the entropy coded side
+//
+// The Huffman decoder produces coefficients in zigzag order, the functions
+// of idct.rs want natural order f[v*8 + u]. The `*_into` / `*_plane` /
+// `*_dequant` functions here take zigzag ordered coefficients and read them
+// through the permutation in the first pass's loads, so the block is never
+// reordered in memory; the output is identical to the natural order function
+// on to_natural(zz). Quantization tables stay in natural order.
+//
+// On the encoder side the `*_quantize` functions write the quantized
+// coefficients in zigzag order, the permutation folded into the quantize
+// loop. idct_into / idct_plane / fdct cover every other transform through
+// the traits, with an explicit reorder.
+
+use crate::fdct::{self, Reciprocal};
+use crate::idct;
+use crate::transform::{ForwardDct, InverseDct};
+
+// NATURAL_ORDER[k] is the natural index of the k-th coefficient in zigzag
+// order (libjpeg's jpeg_natural_order)
+pub const NATURAL_ORDER: [usize;64] = [
+     0,  1,  8, 16,  9,  2,  3, 10,
+    17, 24, 32, 25, 18, 11,  4,  5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13,  6,  7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63];
+
+// ZIGZAG_ORDER[v*8 + u] is the zigzag index of coefficient (v, u), the
+// inverse of NATURAL_ORDER (the matrix of Figure A.6)
+pub const ZIGZAG_ORDER: [usize;64] = [
+     0,  1,  5,  6, 14, 15, 27, 28,
+     2,  4,  7, 13, 16, 26, 29, 42,
+     3,  8, 12, 17, 25, 30, 41, 43,
+     9, 11, 18, 24, 31, 40, 44, 53,
+    10, 19, 23, 32, 39, 45, 52, 54,
+    20, 22, 33, 38, 46, 51, 55, 60,
+    21, 34, 37, 47, 50, 56, 59, 61,
+    35, 36, 48, 49, 57, 58, 62, 63];
+
+pub fn to_natural<T: Copy>(zz: &[T;64]) -> [T;64] {
+    core::array::from_fn(|i| zz[ZIGZAG_ORDER[i]])
+}
+
+pub fn to_zigzag<T: Copy>(f: &[T;64]) -> [T;64] {
+    core::array::from_fn(|k| f[NATURAL_ORDER[k]])
+}
+
+fn load_f32(zz: &[i32;64]) -> [f32;64] {
+    core::array::from_fn(|i| zz[ZIGZAG_ORDER[i]] as f32)
+}
+
+// as idct::llm_idct_into
+pub fn llm_idct_into(zz: &[i32;64], vals: &mut [u8;64]) {
+    llm_idct_plane(zz, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn llm_idct_plane(zz: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ff = load_f32(zz);
+    idct::llm_idct_inplace(&mut ff);
+    idct::store_truncate(&ff, plane, offset, stride);
+}
+
+// as idct::llm_idct_dequant, table from idct::llm_prepare_quant
+pub fn llm_idct_dequant(zz: &[i32;64], table: &[f32;64], vals: &mut [u8;64]) {
+    let mut ff: [f32;64] = core::array::from_fn(|i| zz[ZIGZAG_ORDER[i]] as f32 * table[i]);
+    idct::llm_idct_kernel::<true>(&mut ff, 1.0);
+    idct::store_truncate(&ff, vals, 0, 8);
+}
+
+// as idct::fast_idct_into
+pub fn fast_idct_into(zz: &[i32;64], vals: &mut [u8;64]) {
+    fast_idct_plane(zz, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn fast_idct_plane(zz: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ff = load_f32(zz);
+    idct::fast_idct_inplace(&mut ff);
+    idct::store_truncate(&ff, plane, offset, stride);
+}
+
+// as idct::fast_idct_dequant, table from idct::fast_prepare_quant
+pub fn fast_idct_dequant(zz: &[i32;64], table: &[f32;64], vals: &mut [u8;64]) {
+    let mut ff: [f32;64] = core::array::from_fn(|i| zz[ZIGZAG_ORDER[i]] as f32 * table[i]);
+    idct::fast_idct_kernel::<true, false>(&mut ff);
+    idct::store_truncate(&ff, vals, 0, 8);
+}
+
+// as idct::ap922_idct_into
+pub fn ap922_idct_into(zz: &[i32;64], vals: &mut [u8;64]) {
+    ap922_idct_plane(zz, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn ap922_idct_plane(zz: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    let mut ff = load_f32(zz);
+    idct::ap922_idct_inplace(&mut ff);
+    idct::store_round(&ff, plane, offset, stride);
+}
+
+// as idct::islow_idct_into
+pub fn islow_idct_into(zz: &[i32;64], vals: &mut [u8;64]) {
+    islow_idct_plane(zz, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn islow_idct_plane(zz: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    idct::islow_idct_kernel::<true>(zz, plane, offset, stride);
+}
+
+// as idct::ifast_idct_dequant, table from idct::ifast_prepare_quant
+pub fn ifast_idct_dequant(zz: &[i32;64], table: &[i32;64], vals: &mut [u8;64]) {
+    ifast_idct_dequant_plane(zz, table, vals, 0, 8);
+}
+
+// row y of the block is written to plane[offset + y * stride..][..8]
+pub fn ifast_idct_dequant_plane(zz: &[i32;64], table: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    idct::ifast_idct_kernel::<true>(zz, table, plane, offset, stride);
+}
+
+// any InverseDct, reordered first
+pub fn idct_into<T: InverseDct>(zz: &[i32;64], vals: &mut [u8;64]) {
+    T::idct_into(&to_natural(zz), vals);
+}
+
+pub fn idct_plane<T: InverseDct>(zz: &[i32;64], plane: &mut [u8], offset: usize, stride: usize) {
+    T::idct_plane(&to_natural(zz), plane, offset, stride);
+}
+
+// any ForwardDct, coefficients in zigzag order
+pub fn fdct<T: ForwardDct>(f: &[u8]) -> Vec<f32> {
+    let out = T::fdct(f);
+    NATURAL_ORDER.iter().map(|i| out[*i]).collect()
+}
+
+// as fdct::aan_fdct_quantize, out in zigzag order
+pub fn aan_fdct_quantize(f: &[u8;64], divisors: &[f32;64], out: &mut [i32;64]) {
+    let mut ws = [0_f32;64];
+    fdct::aan_fdct_scaled(f, &mut ws);
+    for (o, i) in out.iter_mut().zip(NATURAL_ORDER) {
+        *o = (ws[i] * divisors[i] + 16384.5) as i32 - 16384;
+    }
+}
+
+// as fdct::ifast_fdct_quantize, out in zigzag order
+pub fn ifast_fdct_quantize(f: &[u8;64], divisors: &[Reciprocal;64], out: &mut [i32;64]) {
+    let mut ws = [0_i32;64];
+    fdct::ifast_fdct_scaled(f, &mut ws);
+    for (o, i) in out.iter_mut().zip(NATURAL_ORDER) {
+        *o = divisors[i].divide(ws[i]);
+    }
+}
+
+// as fdct::islow_fdct_quantize, out in zigzag order
+pub fn islow_fdct_quantize(f: &[u8;64], divisors: &[Reciprocal;64], out: &mut [i32;64]) {
+    let mut ws = [0_i32;64];
+    fdct::islow_fdct_scaled(f, &mut ws);
+    for (o, i) in out.iter_mut().zip(NATURAL_ORDER) {
+        *o = divisors[i].divide(ws[i]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::accuracy::{random_corpus, Ieee1180Rand};
+    use crate::transform::{Idct9, LlmFdct, StandardIdct};
+
+    fn corpus() -> Vec<[i32;64]> {
+        let mut corpus = Vec::new();
+        for nonzero in [1, 3, 10, 64] {
+            corpus.extend(random_corpus(500, 300, nonzero));
+        }
+        corpus
+    }
+
+    #[test]
+    fn tables_are_figure_a6() {
+        // the scan walks the anti diagonals u + v = 0, 1, .. 14, alternating
+        let mut k = 0;
+        for s in 0..15_usize {
+            let v_range: Vec<usize> = (s.saturating_sub(7)..=s.min(7)).collect();
+            // odd diagonals go down (v rising), even ones up
+            let vs: Vec<usize> = if s % 2 == 1 { v_range } else { v_range.into_iter().rev().collect() };
+            for v in vs {
+                assert_eq!(NATURAL_ORDER[k], v * 8 + s - v, "zigzag index {}", k);
+                k += 1;
+            }
+        }
+        for k in 0..64 {
+            assert_eq!(ZIGZAG_ORDER[NATURAL_ORDER[k]], k);
+        }
+    }
+
+    #[test]
+    fn reorder_round_trip() {
+        let f: [i32;64] = core::array::from_fn(|i| i as i32);
+        let zz = to_zigzag(&f);
+        assert_eq!(&zz[..6], &[0, 1, 8, 16, 9, 2]);
+        assert_eq!(to_natural(&zz), f);
+    }
+
+    #[test]
+    fn idct_matches_natural_order() {
+        let q: [u16;64] = core::array::from_fn(|i| 1 + (i % 9) as u16);
+        let llm_table = idct::llm_prepare_quant(&q);
+        let fast_table = idct::fast_prepare_quant(&q);
+        let ifast_table = idct::ifast_prepare_quant(&q);
+        let mut want = [0_u8;64];
+        let mut got = [0_u8;64];
+        for f in corpus() {
+            let zz = to_zigzag(&f);
+            let check = |want: &[u8;64], got: &[u8;64], name: &str| {
+                assert_eq!(want, got, "{} block {:?}", name, f);
+            };
+            idct::llm_idct_into(&f, &mut want);
+            llm_idct_into(&zz, &mut got);
+            check(&want, &got, "llm");
+            idct::fast_idct_into(&f, &mut want);
+            fast_idct_into(&zz, &mut got);
+            check(&want, &got, "fast");
+            idct::ap922_idct_into(&f, &mut want);
+            ap922_idct_into(&zz, &mut got);
+            check(&want, &got, "ap922");
+            idct::islow_idct_into(&f, &mut want);
+            islow_idct_into(&zz, &mut got);
+            check(&want, &got, "islow");
+
+            // quantized values, small enough for the dequantized block
+            let fq = f.map(|v| v / 9);
+            let zq = to_zigzag(&fq);
+            idct::llm_idct_dequant(&fq, &llm_table, &mut want);
+            llm_idct_dequant(&zq, &llm_table, &mut got);
+            check(&want, &got, "llm dequant");
+            idct::fast_idct_dequant(&fq, &fast_table, &mut want);
+            fast_idct_dequant(&zq, &fast_table, &mut got);
+            check(&want, &got, "fast dequant");
+            idct::ifast_idct_dequant(&fq, &ifast_table, &mut want);
+            ifast_idct_dequant(&zq, &ifast_table, &mut got);
+            check(&want, &got, "ifast dequant");
+
+            idct::idct9_into(&f, &mut want);
+            idct_into::<Idct9>(&zz, &mut got);
+            check(&want, &got, "method9");
+        }
+    }
+
+    #[test]
+    fn plane_matches_into() {
+        const STRIDE: usize = 11;
+        for f in corpus().iter().take(200) {
+            let zz = to_zigzag(f);
+            let mut want = [0_u8;64];
+            let mut plane = vec![0_u8; 7 * STRIDE + 3 + 8];
+            islow_idct_into(&zz, &mut want);
+            islow_idct_plane(&zz, &mut plane, 3, STRIDE);
+            for y in 0..8 {
+                assert_eq!(&plane[3 + y * STRIDE..][..8], &want[y * 8..y * 8 + 8]);
+            }
+            StandardIdct::idct_into(f, &mut want);
+            idct_plane::<StandardIdct>(&zz, &mut plane, 3, STRIDE);
+            for y in 0..8 {
+                assert_eq!(&plane[3 + y * STRIDE..][..8], &want[y * 8..y * 8 + 8]);
+            }
+        }
+    }
+
+    #[test]
+    fn fdct_matches_natural_order() {
+        let q: [u16;64] = core::array::from_fn(|i| 1 + (i % 13) as u16 * 5);
+        let aan = fdct::aan_fdct_divisors(&q);
+        let ifast = fdct::ifast_fdct_divisors(&q);
+        let islow = fdct::islow_fdct_divisors(&q);
+        let mut rand = Ieee1180Rand::new();
+        let mut want = [0_i32;64];
+        let mut got = [0_i32;64];
+        for _ in 0..2000 {
+            let f: [u8;64] = core::array::from_fn(|_| rand.next(0, 255) as u8);
+            fdct::aan_fdct_quantize(&f, &aan, &mut want);
+            aan_fdct_quantize(&f, &aan, &mut got);
+            assert_eq!(to_zigzag(&want), got);
+            fdct::ifast_fdct_quantize(&f, &ifast, &mut want);
+            ifast_fdct_quantize(&f, &ifast, &mut got);
+            assert_eq!(to_zigzag(&want), got);
+            fdct::islow_fdct_quantize(&f, &islow, &mut want);
+            islow_fdct_quantize(&f, &islow, &mut got);
+            assert_eq!(to_zigzag(&want), got);
+
+            let natural: [f32;64] = LlmFdct::fdct(&f).try_into().unwrap();
+            assert_eq!(fdct::<LlmFdct>(&f), to_zigzag(&natural).to_vec());
+        }
+    }
+}